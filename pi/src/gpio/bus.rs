@@ -0,0 +1,152 @@
+use core::marker::PhantomData;
+use std::mem;
+
+use super::{owners, Input, Output, Registers, GPIO, GPIO_BASE};
+use volatile::prelude::*;
+
+/// The most pins a `GpioBus` can hold.
+pub const MAX_BUS_PINS: usize = 32;
+
+/// A group of pins in state `State` that are read or written together.
+///
+/// Bit `i` of the values passed to and returned by the bus corresponds to
+/// the `i`th pin pushed onto it. Each operation accesses the `SET`, `CLR` or
+/// `LEV` register of each bank of pins at most once, rather than once per
+/// pin. The bus owns its pins, which are released when it is dropped.
+pub struct GpioBus<State> {
+    registers: &'static mut Registers,
+    pins:      [u8; MAX_BUS_PINS],
+    len:       usize,
+    /// Bit `i` is set if `pins[i]` was claimed and must be released.
+    claimed:   u32,
+    _state:    PhantomData<State>,
+}
+
+impl<State> GpioBus<State> {
+    /// Returns a new, empty bus.
+    pub fn new() -> GpioBus<State> {
+        GpioBus::from_registers(unsafe { &mut *(GPIO_BASE as *mut Registers) })
+    }
+
+    pub(super) fn from_registers(
+        registers: &'static mut Registers,
+    ) -> GpioBus<State> {
+        GpioBus {
+            registers: registers,
+            pins:      [0; MAX_BUS_PINS],
+            len:       0,
+            claimed:   0,
+            _state:    PhantomData,
+        }
+    }
+
+    /// Adds `gpio` to the bus as its next bit. Returns `gpio` back if the bus
+    /// already holds `MAX_BUS_PINS` pins.
+    pub fn push(&mut self, gpio: GPIO<State>) -> Result<(), GPIO<State>> {
+        if self.len == MAX_BUS_PINS {
+            return Err(gpio);
+        }
+
+        self.pins[self.len] = gpio.pin;
+        if gpio.claimed {
+            self.claimed |= 1 << self.len;
+        }
+        self.len += 1;
+
+        // The bus takes over the pin's claim.
+        mem::forget(gpio);
+        Ok(())
+    }
+
+    /// Returns the pins of the bus, in bit order.
+    pub fn pins(&self) -> &[u8] { &self.pins[..self.len] }
+
+    /// Returns the number of pins on the bus.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns `true` if the bus has no pins.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Releases the pins of the bus so that they can be claimed again.
+    pub fn release(self) {}
+
+    /// Returns the bank masks of the pins whose bits are set in `value`.
+    fn banks(&self, value: u32) -> [u32; 2] {
+        let mut banks = [0; 2];
+        for (i, &pin) in self.pins().iter().enumerate() {
+            if value & (1 << i) != 0 {
+                banks[pin as usize / 32] |= 1 << (pin as usize % 32);
+            }
+        }
+
+        banks
+    }
+}
+
+impl<State> Drop for GpioBus<State> {
+    fn drop(&mut self) {
+        for i in 0..self.len {
+            if self.claimed & (1 << i) != 0 {
+                owners::release(self.pins[i]);
+            }
+        }
+    }
+}
+
+impl GpioBus<Output> {
+    /// Sets (turns on) the pins whose bits are set in `mask`.
+    pub fn set(&mut self, mask: u32) {
+        let banks = self.banks(mask);
+        for (bank, &bits) in banks.iter().enumerate() {
+            if bits != 0 {
+                self.registers.SET[bank].write(bits);
+            }
+        }
+    }
+
+    /// Clears (turns off) the pins whose bits are set in `mask`.
+    pub fn clear(&mut self, mask: u32) {
+        let banks = self.banks(mask);
+        for (bank, &bits) in banks.iter().enumerate() {
+            if bits != 0 {
+                self.registers.CLR[bank].write(bits);
+            }
+        }
+    }
+
+    /// Sets the pins whose bits are set in `value` and clears the others.
+    ///
+    /// The write is not atomic. All of the pins to be set are set first,
+    /// with one `SET` write per bank, and only then are the others cleared,
+    /// with one `CLR` write per bank. Each pin is written once, so it goes
+    /// straight from its old level to its new one and never glitches; in
+    /// between, the bus reads as the old and new values ORed together.
+    /// Other pins are never touched.
+    pub fn write(&mut self, value: u32) {
+        self.set(value);
+        self.clear(!value);
+    }
+}
+
+impl GpioBus<Input> {
+    /// Reads the levels of the pins: bit `i` is set if the `i`th pin is
+    /// high.
+    pub fn read(&self) -> u32 {
+        let used = self.banks(!0);
+        let mut levels = [0; 2];
+        for bank in 0..2 {
+            if used[bank] != 0 {
+                levels[bank] = self.registers.LEV[bank].read();
+            }
+        }
+
+        let mut value = 0;
+        for (i, &pin) in self.pins().iter().enumerate() {
+            if levels[pin as usize / 32] & (1 << (pin as usize % 32)) != 0 {
+                value |= 1 << i;
+            }
+        }
+
+        value
+    }
+}
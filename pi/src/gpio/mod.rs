@@ -0,0 +1,472 @@
+use core::marker::PhantomData;
+use std::fmt;
+use std::mem;
+use std::ptr;
+
+use common::IO_BASE;
+use timer::spin_sleep_us;
+use volatile::{ReadVolatile, Reserved, Volatile, WriteVolatile};
+use volatile::prelude::*;
+
+mod bus;
+mod owners;
+#[cfg(test)]
+mod tests;
+
+pub use self::bus::{GpioBus, MAX_BUS_PINS};
+pub use self::owners::{ClaimError, PINS};
+
+/// An alternative GPIO function.
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Function {
+    Input = 0b000,
+    Output = 0b001,
+    Alt0 = 0b100,
+    Alt1 = 0b101,
+    Alt2 = 0b110,
+    Alt3 = 0b111,
+    Alt4 = 0b011,
+    Alt5 = 0b010,
+}
+
+/// The internal resistor pulling an input pin's level when nothing drives
+/// it.
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Pull {
+    Off = 0b00,
+    Down = 0b01,
+    Up = 0b10,
+}
+
+/// A change or level of an input pin that can be detected, setting the
+/// pin's bit in the event detect status register and raising the GPIO
+/// interrupts.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Event {
+    /// A low to high transition, sampled with the system clock.
+    RisingEdge,
+    /// A high to low transition, sampled with the system clock.
+    FallingEdge,
+    /// The pin being high, for as long as it is.
+    HighLevel,
+    /// The pin being low, for as long as it is.
+    LowLevel,
+    /// A low to high transition, detected without sampling so that very
+    /// short pulses are seen.
+    AsyncRisingEdge,
+    /// A high to low transition, detected without sampling.
+    AsyncFallingEdge,
+}
+
+/// How long to wait at each step of the `PUD`/`PUDCLK` sequence, which must
+/// be at least 150 core clock cycles.
+const PULL_SETUP_US: u64 = 1;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    FSEL:   [Volatile<u32>; 6],
+    __r0:   Reserved<u32>,
+    SET:    [WriteVolatile<u32>; 2],
+    __r1:   Reserved<u32>,
+    CLR:    [WriteVolatile<u32>; 2],
+    __r2:   Reserved<u32>,
+    LEV:    [ReadVolatile<u32>; 2],
+    __r3:   Reserved<u32>,
+    EDS:    [Volatile<u32>; 2],
+    __r4:   Reserved<u32>,
+    REN:    [Volatile<u32>; 2],
+    __r5:   Reserved<u32>,
+    FEN:    [Volatile<u32>; 2],
+    __r6:   Reserved<u32>,
+    HEN:    [Volatile<u32>; 2],
+    __r7:   Reserved<u32>,
+    LEN:    [Volatile<u32>; 2],
+    __r8:   Reserved<u32>,
+    AREN:   [Volatile<u32>; 2],
+    __r9:   Reserved<u32>,
+    AFEN:   [Volatile<u32>; 2],
+    __r10:  Reserved<u32>,
+    PUD:    Volatile<u32>,
+    PUDCLK: [Volatile<u32>; 2],
+}
+
+pub enum Uninitialized {}
+pub enum Input {}
+pub enum Output {}
+pub enum Alt {}
+
+/// A GPIO pin in state `State`.
+///
+/// The `State` generic always corresponds to an uninstantiatable type that is
+/// use solely to mark and track the state of a given GPIO pin. A `Gpio`
+/// structure starts in the `Uninitialized` state and must be transitions into
+/// one of `Input`, `Output`, or `Alt` via the `into_input`, `into_output`, and
+/// `into_alt` methods before it can be used.
+///
+/// A `GPIO` owns its pin: no other `GPIO` can be created for the pin until it
+/// is released with `release` or dropped. The pin keeps its function when it
+/// is released.
+pub struct GPIO<State> {
+    pin:       u8,
+    registers: &'static mut Registers,
+    claimed:   bool,
+    _state:    PhantomData<State>,
+}
+
+/// The function, level and owner of a pin, as listed by `pins`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PinStatus {
+    pub pin:      u8,
+    pub function: Function,
+    /// `true` if the pin is high.
+    pub level:    bool,
+    /// The name given to `GPIO::claim` by the pin's owner, if it is owned.
+    pub owner:    Option<&'static str>,
+}
+
+/// Returns the function, level and owner of every pin.
+pub fn pins() -> [PinStatus; PINS] {
+    pins_from(unsafe { &*(GPIO_BASE as *const Registers) })
+}
+
+fn pins_from(registers: &Registers) -> [PinStatus; PINS] {
+    let owners = owners::owners();
+    let mut pins = [PinStatus {
+        pin:      0,
+        function: Function::Input,
+        level:    false,
+        owner:    None,
+    }; PINS];
+
+    for (pin, status) in pins.iter_mut().enumerate() {
+        status.pin = pin as u8;
+        status.function = registers.function(pin as u8);
+        status.level = registers.level(pin as u8);
+        status.owner = owners[pin];
+    }
+
+    pins
+}
+
+/// The base address of the `GPIO` registers.
+const GPIO_BASE: usize = IO_BASE + 0x200000;
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            Function::Input => "input",
+            Function::Output => "output",
+            Function::Alt0 => "alt0",
+            Function::Alt1 => "alt1",
+            Function::Alt2 => "alt2",
+            Function::Alt3 => "alt3",
+            Function::Alt4 => "alt4",
+            Function::Alt5 => "alt5",
+        })
+    }
+}
+
+impl Function {
+    /// Returns the function selected by the 3-bit `FSEL` field `bits`.
+    fn from_bits(bits: u32) -> Function {
+        match bits & 0b111 {
+            0b000 => Function::Input,
+            0b001 => Function::Output,
+            0b100 => Function::Alt0,
+            0b101 => Function::Alt1,
+            0b110 => Function::Alt2,
+            0b111 => Function::Alt3,
+            0b011 => Function::Alt4,
+            _ => Function::Alt5,
+        }
+    }
+}
+
+impl Registers {
+    /// Returns the function selected for pin `pin`.
+    fn function(&self, pin: u8) -> Function {
+        let reg = pin as usize / 10;
+        let bit = (pin as usize % 10) * 3;
+        Function::from_bits(self.FSEL[reg].read() >> bit)
+    }
+
+    /// Returns `true` if pin `pin` is high.
+    fn level(&self, pin: u8) -> bool {
+        let reg = pin as usize / 32;
+        let bit = pin as usize % 32;
+        self.LEV[reg].has_mask(1 << bit)
+    }
+
+    /// Returns the registers enabling detection of `event`.
+    fn event_enable(&mut self, event: Event) -> &mut [Volatile<u32>; 2] {
+        match event {
+            Event::RisingEdge => &mut self.REN,
+            Event::FallingEdge => &mut self.FEN,
+            Event::HighLevel => &mut self.HEN,
+            Event::LowLevel => &mut self.LEN,
+            Event::AsyncRisingEdge => &mut self.AREN,
+            Event::AsyncFallingEdge => &mut self.AFEN,
+        }
+    }
+}
+
+impl<T> GPIO<T> {
+    /// Transitions `self` to state `S`, consuming `self` and returning a new
+    /// `Gpio` instance in state `S`. This method should _never_ be exposed to
+    /// the public!
+    #[inline(always)]
+    fn transition<S>(self) -> GPIO<S> {
+        // `self` must not be dropped, which would release the pin.
+        let registers = unsafe { ptr::read(&self.registers) };
+        let gpio = GPIO {
+            pin:       self.pin,
+            registers: registers,
+            claimed:   self.claimed,
+            _state:    PhantomData,
+        };
+
+        mem::forget(self);
+        gpio
+    }
+
+    /// Returns the pin number.
+    pub fn pin(&self) -> u8 { self.pin }
+
+    /// Returns the function currently selected for the pin.
+    pub fn function(&self) -> Function { self.registers.function(self.pin) }
+
+    /// Reads the pin's value, whatever its function: the level driven by
+    /// the pin for outputs and the level applied to it otherwise. Returns
+    /// `true` if the level is high and `false` if the level is low.
+    pub fn level(&self) -> bool { self.registers.level(self.pin) }
+
+    /// Releases the pin so that it can be claimed again.
+    pub fn release(self) {}
+}
+
+impl<T> Drop for GPIO<T> {
+    fn drop(&mut self) {
+        if self.claimed {
+            owners::release(self.pin);
+        }
+    }
+}
+
+impl GPIO<Uninitialized> {
+    /// Returns a new `GPIO` structure for pin number `pin`, claimed by
+    /// `"gpio"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pin` > `53` or if the pin is already claimed.
+    pub fn new(pin: u8) -> GPIO<Uninitialized> {
+        match GPIO::claim(pin, "gpio") {
+            Ok(gpio) => gpio,
+            Err(e) => panic!("Gpio::new(): {}", e),
+        }
+    }
+
+    /// Returns a new `GPIO` structure for pin number `pin`, recording `owner`
+    /// as its owner in the table returned by `pins`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClaimError::InvalidPin` if `pin` > `53` and
+    /// `ClaimError::Claimed` if another `GPIO` for the pin exists.
+    pub fn claim(
+        pin: u8,
+        owner: &'static str,
+    ) -> Result<GPIO<Uninitialized>, ClaimError> {
+        owners::claim(pin, owner)?;
+
+        let registers = unsafe { &mut *(GPIO_BASE as *mut Registers) };
+        let mut gpio = GPIO::from_registers(pin, registers);
+        gpio.claimed = true;
+        Ok(gpio)
+    }
+
+    /// Returns a new `GPIO` structure for pin number `pin` controlled by
+    /// `registers`, without claiming the pin.
+    fn from_registers(
+        pin: u8,
+        registers: &'static mut Registers,
+    ) -> GPIO<Uninitialized> {
+        GPIO {
+            registers: registers,
+            pin:       pin,
+            claimed:   false,
+            _state:    PhantomData,
+        }
+    }
+
+    /// Enables the alternative function `function` for `self`. Consumes self
+    /// and returns a `Gpio` structure in the `Alt` state.
+    pub fn into_alt(self, function: Function) -> GPIO<Alt> {
+        let reg = self.pin as usize / 10;
+        let bit = (self.pin as usize % 10) * 3;
+
+        let val = self.registers.FSEL[reg].read() & !(0b111 << bit);
+        let val = val | (function as u32) << bit;
+        self.registers.FSEL[reg].write(val);
+
+        self.transition()
+    }
+
+    /// Sets this pin to be an _output_ pin. Consumes self and returns a `Gpio`
+    /// structure in the `Output` state.
+    pub fn into_output(self) -> GPIO<Output> {
+        self.into_alt(Function::Output).transition()
+    }
+
+    /// Sets this pin to be an _input_ pin. Consumes self and returns a `Gpio`
+    /// structure in the `Input` state.
+    pub fn into_input(self) -> GPIO<Input> {
+        self.into_alt(Function::Input).transition()
+    }
+}
+
+impl GPIO<Output> {
+    /// Sets (turns on) the pin.
+    pub fn set(&mut self) {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+        self.registers.SET[reg].write(1 << bit)
+    }
+
+    /// Clears (turns off) the pin.
+    pub fn clear(&mut self) {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+        self.registers.CLR[reg].write(1 << bit)
+    }
+}
+
+impl GPIO<Input> {
+    /// Enables the internal pull-up or pull-down resistor of the pin, or
+    /// disables both, with the timed `PUD`/`PUDCLK` sequence from page 101 of
+    /// the BCM2837 documentation. The setting persists until changed, even
+    /// across function changes.
+    pub fn set_pull(&mut self, pull: Pull) {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+
+        self.registers.PUD.write(pull as u32);
+        spin_sleep_us(PULL_SETUP_US);
+        self.registers.PUDCLK[reg].write(1 << bit);
+        spin_sleep_us(PULL_SETUP_US);
+        self.registers.PUD.write(0);
+        self.registers.PUDCLK[reg].write(0);
+    }
+
+    /// Enables detection of `event` on the pin. Several events may be enabled
+    /// at once.
+    pub fn enable_event(&mut self, event: Event) {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+        self.registers.event_enable(event)[reg].or_mask(1 << bit);
+    }
+
+    /// Disables detection of `event` on the pin.
+    pub fn disable_event(&mut self, event: Event) {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+        self.registers.event_enable(event)[reg].and_mask(!(1 << bit));
+    }
+
+    /// Returns `true` if an enabled event has been detected on the pin since
+    /// the status was last cleared.
+    pub fn event_detected(&self) -> bool {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+        self.registers.EDS[reg].has_mask(1 << bit)
+    }
+
+    /// Clears the pin's event detect status. A level event that still holds
+    /// is detected again immediately.
+    pub fn clear_event(&mut self) {
+        let reg = self.pin as usize / 32;
+        let bit = self.pin as usize % 32;
+        self.registers.EDS[reg].write(1 << bit);
+    }
+}
+
+/// A function handling an event detected on the pin it is given.
+pub type EventHandler = fn(u8);
+
+/// Per-pin handlers for detected GPIO events.
+///
+/// The kernel's IRQ handler should call `dispatch` when any of
+/// `Interrupt::Gpio0` to `Interrupt::Gpio3` is pending; `Gpio3` is raised for
+/// events on every pin. Events are enabled per pin with
+/// `GPIO<Input>::enable_event`.
+pub struct EventHandlers {
+    registers: &'static mut Registers,
+    handlers:  [Option<EventHandler>; PINS],
+}
+
+impl EventHandlers {
+    /// Returns a new `EventHandlers` with no handlers registered.
+    pub fn new() -> EventHandlers {
+        let registers = unsafe { &mut *(GPIO_BASE as *mut Registers) };
+        EventHandlers::from_registers(registers)
+    }
+
+    fn from_registers(registers: &'static mut Registers) -> EventHandlers {
+        EventHandlers {
+            registers: registers,
+            handlers:  [None; PINS],
+        }
+    }
+
+    /// Calls `handler` for events detected on pin `pin`, replacing any
+    /// handler registered before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pin` > `53`.
+    pub fn register(&mut self, pin: u8, handler: EventHandler) {
+        if pin > 53 {
+            panic!(
+                "EventHandlers::register(): pin {} exceeds maximum of 53",
+                pin
+            );
+        }
+
+        self.handlers[pin as usize] = Some(handler);
+    }
+
+    /// Removes the handler of pin `pin`, if any.
+    pub fn unregister(&mut self, pin: u8) {
+        if let Some(handler) = self.handlers.get_mut(pin as usize) {
+            *handler = None;
+        }
+    }
+
+    /// Clears the event detect status of every pin with a detected event and
+    /// calls its handler, if one is registered, with the pin number. The
+    /// status is cleared before the handler runs, so events detected while it
+    /// runs are not lost.
+    pub fn dispatch(&mut self) {
+        for reg in 0..2 {
+            let status = self.registers.EDS[reg].read();
+            if status == 0 {
+                continue;
+            }
+
+            self.registers.EDS[reg].write(status);
+            for bit in 0..32 {
+                let pin = reg * 32 + bit;
+                if status & (1 << bit) == 0 || pin >= self.handlers.len() {
+                    continue;
+                }
+
+                if let Some(handler) = self.handlers[pin] {
+                    handler(pin as u8);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+/// The number of GPIO pins.
+pub const PINS: usize = 54;
+
+/// The error returned when a pin cannot be claimed.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClaimError {
+    /// The pin number exceeds the maximum of 53.
+    InvalidPin(u8),
+    /// The pin is already owned by the named owner.
+    Claimed(u8, &'static str),
+}
+
+impl fmt::Display for ClaimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClaimError::InvalidPin(pin) => {
+                write!(f, "pin {} exceeds maximum of 53", pin)
+            }
+            ClaimError::Claimed(pin, owner) => {
+                write!(f, "pin {} is owned by {}", pin, owner)
+            }
+        }
+    }
+}
+
+/// Guards `OWNERS`.
+static LOCKED: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// The owner of each pin, if any.
+static mut OWNERS: [Option<&'static str>; PINS] = [None; PINS];
+
+/// Calls `f` with exclusive access to the table of owners.
+fn with_owners<R, F>(f: F) -> R
+where
+    F: FnOnce(&mut [Option<&'static str>; PINS]) -> R,
+{
+    while LOCKED.compare_and_swap(false, true, Ordering::Acquire) {}
+    let result = f(unsafe { &mut OWNERS });
+    LOCKED.store(false, Ordering::Release);
+    result
+}
+
+/// Records `owner` as the owner of pin `pin` if it has none.
+pub fn claim(pin: u8, owner: &'static str) -> Result<(), ClaimError> {
+    if pin as usize >= PINS {
+        return Err(ClaimError::InvalidPin(pin));
+    }
+
+    with_owners(|owners| match owners[pin as usize] {
+        Some(current) => Err(ClaimError::Claimed(pin, current)),
+        None => {
+            owners[pin as usize] = Some(owner);
+            Ok(())
+        }
+    })
+}
+
+/// Removes the owner of pin `pin`.
+pub fn release(pin: u8) {
+    with_owners(|owners| owners[pin as usize] = None);
+}
+
+/// Returns the owner of every pin.
+pub fn owners() -> [Option<&'static str>; PINS] {
+    with_owners(|owners| *owners)
+}
@@ -0,0 +1,433 @@
+use std::cell::RefCell;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+use super::*;
+use timer::mock;
+
+/// Returns a zeroed register block in memory standing in for the GPIO
+/// registers.
+fn mock_registers() -> &'static mut Registers {
+    unsafe { &mut *Box::into_raw(Box::new(mem::zeroed())) }
+}
+
+/// Reads the `u32` register at `address`.
+fn read(address: usize) -> u32 {
+    unsafe { ptr::read_volatile(address as *const u32) }
+}
+
+/// Returns the addresses of `PUD`, `PUDCLK0` and `PUDCLK1`.
+fn pull_registers(registers: &mut Registers) -> [usize; 3] {
+    [
+        &mut registers.PUD as *mut _ as usize,
+        &mut registers.PUDCLK[0] as *mut _ as usize,
+        &mut registers.PUDCLK[1] as *mut _ as usize,
+    ]
+}
+
+/// Reads the registers at `addresses`.
+fn read_all(addresses: [usize; 3]) -> [u32; 3] {
+    [read(addresses[0]), read(addresses[1]), read(addresses[2])]
+}
+
+/// Returns the distinct consecutive states in `history`.
+fn steps(history: &[[u32; 3]]) -> Vec<[u32; 3]> {
+    let mut steps: Vec<[u32; 3]> = Vec::new();
+    for state in history {
+        if steps.last() != Some(state) {
+            steps.push(*state);
+        }
+    }
+
+    steps
+}
+
+/// Sets the pull of `pin` on a mocked register block. Returns `PUD`,
+/// `PUDCLK0` and `PUDCLK1` as seen at each tick of the clock, and after
+/// `set_pull` returns.
+fn set_pull(pin: u8, pull: Pull) -> (Vec<[u32; 3]>, [u32; 3]) {
+    let registers = mock_registers();
+    let addresses = pull_registers(registers);
+
+    let history = Rc::new(RefCell::new(Vec::new()));
+    let recorder = history.clone();
+    mock::on_tick(move |_| recorder.borrow_mut().push(read_all(addresses)));
+
+    GPIO::from_registers(pin, registers).into_input().set_pull(pull);
+
+    let history = history.borrow().clone();
+    (history, read_all(addresses))
+}
+
+#[test]
+fn pull_up_sequence() {
+    let (history, after) = set_pull(4, Pull::Up);
+
+    // Control signal set, then clocked into pin 4, waiting at each step.
+    assert_eq!(steps(&history), vec![[0b10, 0, 0], [0b10, 1 << 4, 0]]);
+    let clocked = history.iter().filter(|s| s[1] != 0).count() as u64;
+    assert!(clocked >= PULL_SETUP_US);
+    assert!(history.len() as u64 - clocked >= PULL_SETUP_US);
+
+    // Both are cleared afterwards.
+    assert_eq!(after, [0, 0, 0]);
+}
+
+#[test]
+fn pull_down_sequence() {
+    let (history, after) = set_pull(17, Pull::Down);
+
+    assert_eq!(steps(&history), vec![[0b01, 0, 0], [0b01, 1 << 17, 0]]);
+    assert_eq!(after, [0, 0, 0]);
+}
+
+#[test]
+fn pull_off_sequence_on_second_bank() {
+    let (history, after) = set_pull(40, Pull::Off);
+
+    assert_eq!(steps(&history), vec![[0, 0, 0], [0, 0, 1 << 8]]);
+    assert_eq!(after, [0, 0, 0]);
+}
+
+#[test]
+fn set_pull_keeps_function() {
+    let registers = mock_registers();
+    let fsel = &mut registers.FSEL[1] as *mut _ as usize;
+    unsafe { ptr::write_volatile(fsel as *mut u32, 0b111 << 9) };
+
+    let mut pin = GPIO::from_registers(13, registers).into_input();
+    assert_eq!(read(fsel), 0);
+    pin.set_pull(Pull::Up);
+    assert_eq!(read(fsel), 0);
+}
+
+#[test]
+fn enable_and_disable_events() {
+    let registers = mock_registers();
+    let ren1 = &mut registers.REN[1] as *mut _ as usize;
+    let afen1 = &mut registers.AFEN[1] as *mut _ as usize;
+    let len0 = &mut registers.LEN[0] as *mut _ as usize;
+
+    let mut pin = GPIO::from_registers(35, registers).into_input();
+    pin.enable_event(Event::RisingEdge);
+    pin.enable_event(Event::AsyncFallingEdge);
+    assert_eq!(read(ren1), 1 << 3);
+    assert_eq!(read(afen1), 1 << 3);
+    assert_eq!(read(len0), 0);
+
+    pin.disable_event(Event::RisingEdge);
+    assert_eq!(read(ren1), 0);
+    assert_eq!(read(afen1), 1 << 3);
+}
+
+#[test]
+fn enabling_events_keeps_other_pins() {
+    let registers = mock_registers();
+    let hen0 = &mut registers.HEN[0] as *mut _ as usize;
+    unsafe { ptr::write_volatile(hen0 as *mut u32, 1 << 2) };
+
+    let mut pin = GPIO::from_registers(9, registers).into_input();
+    pin.enable_event(Event::HighLevel);
+    assert_eq!(read(hen0), 1 << 2 | 1 << 9);
+    pin.disable_event(Event::HighLevel);
+    assert_eq!(read(hen0), 1 << 2);
+}
+
+#[test]
+fn poll_and_clear_event() {
+    let registers = mock_registers();
+    let eds0 = &mut registers.EDS[0] as *mut _ as usize;
+
+    let mut pin = GPIO::from_registers(21, registers).into_input();
+    assert!(!pin.event_detected());
+
+    unsafe { ptr::write_volatile(eds0 as *mut u32, 1 << 21 | 1 << 4) };
+    assert!(pin.event_detected());
+
+    // The status register is write-1-to-clear: only the pin's bit is written.
+    pin.clear_event();
+    assert_eq!(read(eds0), 1 << 21);
+}
+
+thread_local! {
+    static HANDLED: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+}
+
+fn record_event(pin: u8) {
+    HANDLED.with(|handled| handled.borrow_mut().push(pin));
+}
+
+#[test]
+fn dispatch_calls_registered_handlers() {
+    let registers = mock_registers();
+    let eds = [
+        &mut registers.EDS[0] as *mut _ as usize,
+        &mut registers.EDS[1] as *mut _ as usize,
+    ];
+
+    let mut handlers = EventHandlers::from_registers(registers);
+    handlers.register(3, record_event);
+    handlers.register(40, record_event);
+    handlers.register(7, record_event);
+    handlers.unregister(7);
+
+    unsafe {
+        ptr::write_volatile(eds[0] as *mut u32, 1 << 3 | 1 << 5 | 1 << 7);
+        ptr::write_volatile(eds[1] as *mut u32, 1 << 8);
+    }
+    handlers.dispatch();
+
+    HANDLED.with(|handled| assert_eq!(*handled.borrow(), vec![3, 40]));
+
+    // Every detected event is acknowledged, handled or not.
+    assert_eq!(read(eds[0]), 1 << 3 | 1 << 5 | 1 << 7);
+    assert_eq!(read(eds[1]), 1 << 8);
+}
+
+#[test]
+#[should_panic]
+fn register_rejects_invalid_pin() {
+    EventHandlers::from_registers(mock_registers()).register(54, record_event);
+}
+
+// Pins are claimed in a table shared by every test, so each of the
+// following tests uses its own pins.
+
+#[test]
+fn claim_is_exclusive() {
+    let first = GPIO::claim(50, "first").unwrap();
+    let error = GPIO::claim(50, "second").err();
+    assert_eq!(error, Some(ClaimError::Claimed(50, "first")));
+
+    first.release();
+    let second = GPIO::claim(50, "second").unwrap();
+    assert_eq!(second.pin(), 50);
+}
+
+#[test]
+fn dropping_releases_pin() {
+    {
+        let _gpio = GPIO::claim(51, "scoped").unwrap();
+        assert!(GPIO::claim(51, "other").is_err());
+    }
+
+    assert!(GPIO::claim(51, "other").is_ok());
+}
+
+#[test]
+fn claim_rejects_invalid_pin() {
+    assert_eq!(GPIO::claim(54, "test").err(), Some(ClaimError::InvalidPin(54)));
+}
+
+#[test]
+fn transitions_keep_claim() {
+    owners::claim(45, "test").unwrap();
+    let mut gpio = GPIO::from_registers(45, mock_registers());
+    gpio.claimed = true;
+
+    let output = gpio.into_output();
+    assert!(GPIO::claim(45, "other").is_err());
+
+    drop(output);
+    assert!(GPIO::claim(45, "other").is_ok());
+}
+
+#[test]
+fn pins_lists_functions_and_owners() {
+    let registers = mock_registers();
+    let fsel4 = &mut registers.FSEL[4] as *mut _ as usize;
+    unsafe { ptr::write_volatile(fsel4 as *mut u32, 0b010 << 3 | 0b001 << 9) };
+    let _owned = GPIO::claim(43, "table").unwrap();
+
+    let pins = pins_from(registers);
+    assert_eq!(pins.len(), PINS);
+    assert_eq!(pins[41].pin, 41);
+    assert_eq!(pins[41].function, Function::Alt5);
+    assert_eq!(pins[43].function, Function::Output);
+    assert_eq!(pins[43].owner, Some("table"));
+    assert_eq!(pins[44].function, Function::Input);
+    assert_eq!(pins[44].owner, None);
+}
+
+#[test]
+fn function_bits_round_trip() {
+    let functions = [
+        Function::Input,
+        Function::Output,
+        Function::Alt0,
+        Function::Alt1,
+        Function::Alt2,
+        Function::Alt3,
+        Function::Alt4,
+        Function::Alt5,
+    ];
+
+    for &function in functions.iter() {
+        assert_eq!(Function::from_bits(function as u32), function);
+    }
+}
+
+/// Returns an output bus of `pins` on `registers`.
+fn output_bus(registers: *mut Registers, pins: &[u8]) -> GpioBus<Output> {
+    let mut bus = GpioBus::from_registers(unsafe { &mut *registers });
+    for &pin in pins {
+        let gpio = GPIO::from_registers(pin, unsafe { &mut *registers });
+        assert!(bus.push(gpio.into_output()).is_ok());
+    }
+
+    bus
+}
+
+#[test]
+fn bus_sets_and_clears_per_bank() {
+    let registers = Box::into_raw(Box::new(unsafe { mem::zeroed() }));
+    let mut bus = output_bus(registers, &[2, 40, 3, 33]);
+    assert_eq!(bus.pins(), &[2, 40, 3, 33]);
+
+    let (set, clr) = unsafe {
+        (
+            [
+                &mut (*registers).SET[0] as *mut _ as usize,
+                &mut (*registers).SET[1] as *mut _ as usize,
+            ],
+            [
+                &mut (*registers).CLR[0] as *mut _ as usize,
+                &mut (*registers).CLR[1] as *mut _ as usize,
+            ],
+        )
+    };
+
+    bus.set(0b1011);
+    assert_eq!(read(set[0]), 1 << 2);
+    assert_eq!(read(set[1]), 1 << 8 | 1 << 1);
+
+    bus.clear(0b0100);
+    assert_eq!(read(clr[0]), 1 << 3);
+    assert_eq!(read(clr[1]), 0);
+}
+
+#[test]
+fn bus_writes_patterns() {
+    let registers = Box::into_raw(Box::new(unsafe { mem::zeroed() }));
+    let mut bus = output_bus(registers, &[4, 5, 6, 7, 50]);
+
+    let (set0, clr0, set1, clr1) = unsafe {
+        (
+            &mut (*registers).SET[0] as *mut _ as usize,
+            &mut (*registers).CLR[0] as *mut _ as usize,
+            &mut (*registers).SET[1] as *mut _ as usize,
+            &mut (*registers).CLR[1] as *mut _ as usize,
+        )
+    };
+
+    // Bits beyond the bus are ignored.
+    bus.write(0b1_0101 | 1 << 31);
+    assert_eq!(read(set0), 1 << 4 | 1 << 6);
+    assert_eq!(read(clr0), 1 << 5 | 1 << 7);
+    assert_eq!(read(set1), 1 << 18);
+    assert_eq!(read(clr1), 0);
+}
+
+#[test]
+fn bus_reads_levels() {
+    let registers = Box::into_raw(Box::new(unsafe { mem::zeroed() }));
+    let mut bus = GpioBus::from_registers(unsafe { &mut *registers });
+    for &pin in &[10, 35, 11] {
+        let gpio = GPIO::from_registers(pin, unsafe { &mut *registers });
+        assert!(bus.push(gpio.into_input()).is_ok());
+    }
+
+    unsafe {
+        let lev0 = &mut (*registers).LEV[0] as *mut _ as *mut u32;
+        let lev1 = &mut (*registers).LEV[1] as *mut _ as *mut u32;
+        ptr::write_volatile(lev0, 1 << 11 | 1 << 12);
+        ptr::write_volatile(lev1, 1 << 3);
+    }
+
+    assert_eq!(bus.read(), 0b110);
+}
+
+#[test]
+fn bus_holds_at_most_max_pins() {
+    let registers = Box::into_raw(Box::new(unsafe { mem::zeroed() }));
+    let pins: Vec<u8> = (0..MAX_BUS_PINS as u8).collect();
+    let mut bus = output_bus(registers, &pins);
+    assert_eq!(bus.len(), MAX_BUS_PINS);
+
+    let extra = GPIO::from_registers(40, unsafe { &mut *registers });
+    assert!(bus.push(extra.into_output()).is_err());
+}
+
+#[test]
+fn bus_releases_claimed_pins() {
+    let registers = mock_registers();
+    owners::claim(47, "bus").unwrap();
+    let mut gpio = GPIO::from_registers(47, registers);
+    gpio.claimed = true;
+
+    let mut bus = GpioBus::new();
+    assert!(bus.push(gpio.into_output()).is_ok());
+    assert!(GPIO::claim(47, "other").is_err());
+
+    bus.release();
+    assert!(GPIO::claim(47, "other").is_ok());
+}
+
+#[test]
+fn function_reads_back_fsel() {
+    let registers: *mut Registers = mock_registers();
+
+    let gpio = GPIO::from_registers(23, unsafe { &mut *registers });
+    assert_eq!(gpio.function(), Function::Input);
+
+    let alt = gpio.into_alt(Function::Alt3);
+    assert_eq!(alt.function(), Function::Alt3);
+
+    // Neighbouring pins in the same `FSEL` register are read independently.
+    let neighbour = GPIO::from_registers(24, unsafe { &mut *registers });
+    let output = neighbour.into_output();
+    assert_eq!(output.function(), Function::Output);
+    assert_eq!(alt.function(), Function::Alt3);
+}
+
+#[test]
+fn input_level_tests_its_own_bit() {
+    let registers: *mut Registers = mock_registers();
+    let lev = unsafe { &mut (*registers).LEV[0] as *mut _ as *mut u32 };
+    let pin = GPIO::from_registers(6, unsafe { &mut *registers }).into_input();
+
+    unsafe { ptr::write_volatile(lev, 0) };
+    assert!(!pin.level());
+
+    // Other pins being high must not make this one read high.
+    unsafe { ptr::write_volatile(lev, !(1 << 6)) };
+    assert!(!pin.level());
+
+    unsafe { ptr::write_volatile(lev, 1 << 6) };
+    assert!(pin.level());
+}
+
+#[test]
+fn output_reads_its_level() {
+    let registers: *mut Registers = mock_registers();
+    let lev = unsafe { &mut (*registers).LEV[1] as *mut _ as *mut u32 };
+    let pin = GPIO::from_registers(47, unsafe { &mut *registers });
+    let pin = pin.into_output();
+
+    assert!(!pin.level());
+    unsafe { ptr::write_volatile(lev, 1 << 15) };
+    assert!(pin.level());
+}
+
+#[test]
+fn pins_lists_levels() {
+    let registers = mock_registers();
+    let lev0 = &mut registers.LEV[0] as *mut _ as *mut u32;
+    unsafe { ptr::write_volatile(lev0, 1 << 12) };
+
+    let pins = pins_from(registers);
+    assert!(pins[12].level);
+    assert!(!pins[11].level);
+    assert!(!pins[13].level);
+}
@@ -0,0 +1,70 @@
+use common::IO_BASE;
+use volatile::{ReadVolatile, Volatile};
+use volatile::prelude::*;
+
+/// The base address of the interrupt controller registers, from page 112 of
+/// the BCM2837 documentation.
+const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
+
+/// An interrupt source routed through the interrupt controller.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Interrupt {
+    Timer1 = 1,
+    Timer3 = 3,
+    Usb = 9,
+    Aux = 29,
+    Gpio0 = 49,
+    Gpio1 = 50,
+    Gpio2 = 51,
+    Gpio3 = 52,
+    Uart = 57,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    IRQ_BASIC_PENDING:  ReadVolatile<u32>,
+    IRQ_PENDING:        [ReadVolatile<u32>; 2],
+    FIQ_CONTROL:        Volatile<u32>,
+    ENABLE_IRQS:        [Volatile<u32>; 2],
+    ENABLE_BASIC_IRQS:  Volatile<u32>,
+    DISABLE_IRQS:       [Volatile<u32>; 2],
+    DISABLE_BASIC_IRQS: Volatile<u32>,
+}
+
+/// An interface to the interrupt controller.
+pub struct Controller {
+    registers: &'static mut Registers,
+}
+
+impl Controller {
+    /// Returns a new handle to the interrupt controller.
+    pub fn new() -> Controller {
+        Controller {
+            registers: unsafe { &mut *(INT_BASE as *mut Registers) },
+        }
+    }
+
+    /// Enables the interrupt `int`.
+    pub fn enable(&mut self, int: Interrupt) {
+        let (reg, bit) = Controller::position(int);
+        self.registers.ENABLE_IRQS[reg].write(1 << bit);
+    }
+
+    /// Disables the interrupt `int`.
+    pub fn disable(&mut self, int: Interrupt) {
+        let (reg, bit) = Controller::position(int);
+        self.registers.DISABLE_IRQS[reg].write(1 << bit);
+    }
+
+    /// Returns `true` if `int` is pending.
+    pub fn is_pending(&self, int: Interrupt) -> bool {
+        let (reg, bit) = Controller::position(int);
+        self.registers.IRQ_PENDING[reg].has_mask(1 << bit)
+    }
+
+    /// Returns the register index and bit of `int`.
+    fn position(int: Interrupt) -> (usize, usize) {
+        (int as usize / 32, int as usize % 32)
+    }
+}
@@ -6,5 +6,9 @@ extern crate volatile;
 pub mod atags;
 pub mod timer;
 pub mod uart;
+pub mod pl011;
 pub mod gpio;
+pub mod interrupt;
 pub mod common;
+
+mod timeout;
@@ -0,0 +1,405 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use common::IO_BASE;
+use gpio::{Alt, ClaimError, Function, GPIO};
+use timer::current_time;
+use timeout::{self, Timeouts, TryRead};
+use volatile::{ReadVolatile, Reserved, Volatile, WriteVolatile};
+use volatile::prelude::*;
+
+/// The base address of the PL011 (UART0) registers, from page 177 of the
+/// BCM2837 documentation.
+const PL011_BASE: usize = IO_BASE + 0x201000;
+
+/// The name the PL011 claims its pins with.
+const OWNER: &str = "pl011";
+
+#[cfg(test)]
+mod tests;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    DR:     Volatile<u32>, // data, with the receive errors of the byte
+    RSRECR: Volatile<u32>, // receive status; writing clears the errors
+    __r0:   [Reserved<u32>; 4],
+    FR:     ReadVolatile<u32>, // flags
+    __r1:   Reserved<u32>,
+    ILPR:   Volatile<u32>, // IrDA, unused on the BCM2837
+    IBRD:   Volatile<u32>, // integer baud rate divisor
+    FBRD:   Volatile<u32>, // fractional baud rate divisor
+    LCRH:   Volatile<u32>, // line control
+    CR:     Volatile<u32>, // control
+    IFLS:   Volatile<u32>, // interrupt FIFO level select
+    IMSC:   Volatile<u32>, // interrupt mask
+    RIS:    ReadVolatile<u32>, // raw interrupt status
+    MIS:    ReadVolatile<u32>, // masked interrupt status
+    ICR:    WriteVolatile<u32>, // interrupt clear
+}
+
+#[repr(u32)]
+enum DrFlags {
+    Framing = 1 << 8,
+    Parity = 1 << 9,
+    Break = 1 << 10,
+    Overrun = 1 << 11,
+}
+
+#[repr(u32)]
+enum FrFlags {
+    Busy = 1 << 3,
+    RxEmpty = 1 << 4,
+    TxFull = 1 << 5,
+}
+
+#[repr(u32)]
+enum LcrhFlags {
+    ParityEnable = 1 << 1,
+    EvenParity = 1 << 2,
+    TwoStopBits = 1 << 3,
+    FifoEnable = 1 << 4,
+}
+
+#[repr(u32)]
+enum CrFlags {
+    Enable = 1,
+    TxEnable = 1 << 8,
+    RxEnable = 1 << 9,
+}
+
+/// The number of data bits in a character.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DataBits {
+    Five = 0b00,
+    Six = 0b01,
+    Seven = 0b10,
+    Eight = 0b11,
+}
+
+/// The parity bit sent after each character, if any.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+/// The number of stop bits sent after each character.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// A FIFO fill level at which the receive or transmit interrupt is raised.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FifoLevel {
+    OneEighth = 0b000,
+    OneQuarter = 0b001,
+    OneHalf = 0b010,
+    ThreeQuarters = 0b011,
+    SevenEighths = 0b100,
+}
+
+/// Line settings for a `PL011`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PL011Config {
+    /// The frequency of the UART reference clock in Hz. The firmware sets it
+    /// to 48 MHz unless `init_uart_clock` is given in `config.txt`.
+    pub clock:        u32,
+    /// The baud rate, which must be reachable from `clock`: at most
+    /// `clock / 16`.
+    pub baud_rate:    u32,
+    pub data_bits:    DataBits,
+    pub parity:       Parity,
+    pub stop_bits:    StopBits,
+    /// Whether to use the 16 byte receive and transmit FIFOs. Without them
+    /// the UART holds a single byte in each direction.
+    pub fifos:        bool,
+    /// The receive FIFO level that raises the receive interrupt.
+    pub rx_threshold: FifoLevel,
+    /// The transmit FIFO level that raises the transmit interrupt.
+    pub tx_threshold: FifoLevel,
+}
+
+impl Default for PL011Config {
+    /// 115200 baud 8N1 from a 48 MHz clock, with FIFOs enabled.
+    fn default() -> PL011Config {
+        PL011Config {
+            clock:        48_000_000,
+            baud_rate:    115200,
+            data_bits:    DataBits::Eight,
+            parity:       Parity::None,
+            stop_bits:    StopBits::One,
+            fifos:        true,
+            rx_threshold: FifoLevel::OneHalf,
+            tx_threshold: FifoLevel::OneHalf,
+        }
+    }
+}
+
+/// Receive errors seen since they were last taken with
+/// `PL011::take_errors`.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Errors {
+    /// A character was missing its stop bit.
+    pub framing: bool,
+    /// A character's parity did not match the configured parity.
+    pub parity:  bool,
+    /// The line was held low for longer than a character, i.e. the sender
+    /// sent a break. The break is received as a `0` byte.
+    pub brk:     bool,
+    /// The receive FIFO was full when a character arrived, which was lost.
+    pub overrun: bool,
+}
+
+impl Errors {
+    /// Returns `true` if any error occurred.
+    pub fn any(&self) -> bool {
+        self.framing || self.parity || self.brk || self.overrun
+    }
+}
+
+/// The BCM2837 PL011 UART (UART0), on GPIO pins 14 and 15.
+///
+/// Unlike the mini UART, its baud rate is derived from a dedicated reference
+/// clock and it supports parity, two stop bits and 16 byte FIFOs.
+pub struct PL011 {
+    registers: &'static mut Registers,
+    timeouts:  Timeouts,
+    errors:    Errors,
+    /// The pins claimed for the UART, released when it is dropped.
+    _pins:     [GPIO<Alt>; 2],
+}
+
+/// Returns the integer and fractional baud rate divisors for `baud_rate`
+/// from a reference clock of `clock` Hz.
+fn divisors(clock: u32, baud_rate: u32) -> Option<(u32, u32)> {
+    if baud_rate == 0 {
+        return None;
+    }
+
+    // The divisor is `clock / (16 * baud_rate)` with a 6 bit fraction.
+    let baud_rate = baud_rate as u64;
+    let divisor = (clock as u64 * 4 + baud_rate / 2) / baud_rate;
+    let (integer, fraction) = (divisor >> 6, divisor & 0x3f);
+    if integer == 0 || integer > 0xffff {
+        return None;
+    }
+
+    Some((integer as u32, fraction as u32))
+}
+
+impl PL011 {
+    /// Returns a new `PL011` configured with `config`, routing it to GPIO
+    /// pins 14 and 15.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.baud_rate` cannot be reached from `config.clock`,
+    /// or if pin 14 or 15 is owned by another driver.
+    pub fn new(config: PL011Config) -> PL011 {
+        match PL011::claim(config) {
+            Ok(uart) => uart,
+            Err(e) => panic!("PL011::new(): {}", e),
+        }
+    }
+
+    /// Returns a new `PL011` configured with `config`, claiming GPIO pins 14
+    /// and 15 for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `ClaimError` for pin 14 or 15 if it is owned by another
+    /// driver. No pin is claimed or changed in that case.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.baud_rate` cannot be reached from `config.clock`.
+    pub fn claim(config: PL011Config) -> Result<PL011, ClaimError> {
+        let baud = divisors(config.clock, config.baud_rate);
+        let (integer, fraction) = match baud {
+            Some(divisors) => divisors,
+            None => panic!(
+                "PL011::claim(): baud rate {} unreachable from a {} Hz clock",
+                config.baud_rate, config.clock
+            ),
+        };
+
+        // Claim both pins before switching either of them to the UART.
+        let tx = GPIO::claim(14, OWNER)?;
+        let rx = GPIO::claim(15, OWNER)?;
+
+        // set pins 14 and 15 to TXD0/RXD0
+        let pins = [tx.into_alt(Function::Alt0), rx.into_alt(Function::Alt0)];
+
+        let registers = unsafe { &mut *(PL011_BASE as *mut Registers) };
+
+        // Disable the UART and let any character in flight finish.
+        registers.CR.write(0);
+        while registers.FR.has_mask(FrFlags::Busy as u32) {}
+
+        // Clearing `FEN` flushes the transmit FIFO.
+        registers.LCRH.write(0);
+        registers.ICR.write(0x7ff);
+        registers.RSRECR.write(0);
+
+        // The divisors are latched by the write to `LCRH` that follows.
+        registers.IBRD.write(integer);
+        registers.FBRD.write(fraction);
+
+        let mut lcrh = (config.data_bits as u32) << 5;
+        match config.parity {
+            Parity::None => {}
+            Parity::Odd => lcrh |= LcrhFlags::ParityEnable as u32,
+            Parity::Even => {
+                lcrh |= LcrhFlags::ParityEnable as u32
+                    | LcrhFlags::EvenParity as u32
+            }
+        }
+        if config.stop_bits == StopBits::Two {
+            lcrh |= LcrhFlags::TwoStopBits as u32;
+        }
+        if config.fifos {
+            lcrh |= LcrhFlags::FifoEnable as u32;
+        }
+        registers.LCRH.write(lcrh);
+
+        registers.IFLS.write(
+            (config.rx_threshold as u32) << 3 | config.tx_threshold as u32,
+        );
+        registers.IMSC.write(0);
+
+        registers.CR.write(
+            CrFlags::Enable as u32
+                | CrFlags::TxEnable as u32
+                | CrFlags::RxEnable as u32,
+        );
+
+        Ok(PL011 {
+            registers,
+            timeouts: Timeouts::default(),
+            errors: Errors::default(),
+            _pins: pins,
+        })
+    }
+
+    /// Sets how long reads wait in total: for the first byte in
+    /// `wait_for_byte` and `io::Read::read`, and for the rest of the buffer
+    /// in the latter. `None`, the default, waits forever.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeouts.read = timeout.map(timeout::micros);
+    }
+
+    /// Sets how long `io::Read::read` waits for each byte after the first
+    /// before returning the bytes it has. `None`, the default, returns as
+    /// soon as no more bytes have been received.
+    pub fn set_inter_byte_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeouts.inter_byte = timeout.map(timeout::micros);
+    }
+
+    /// Returns the receive errors seen since the last call and clears them.
+    pub fn take_errors(&mut self) -> Errors {
+        let errors = self.errors;
+        self.errors = Errors::default();
+        errors
+    }
+
+    /// Reads a byte if one has been received, without blocking. Errors
+    /// flagged with the byte are recorded for `take_errors`.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        if !self.has_byte() {
+            return None;
+        }
+
+        let data = self.registers.DR.read();
+        self.errors.framing |= data & DrFlags::Framing as u32 != 0;
+        self.errors.parity |= data & DrFlags::Parity as u32 != 0;
+        self.errors.brk |= data & DrFlags::Break as u32 != 0;
+        self.errors.overrun |= data & DrFlags::Overrun as u32 != 0;
+
+        Some(data as u8)
+    }
+
+    /// Writes `byte` if there is room for it in the transmit FIFO, without
+    /// blocking. Returns `Err(())` if there is no room.
+    pub fn try_write_byte(&mut self, byte: u8) -> Result<(), ()> {
+        if self.registers.FR.has_mask(FrFlags::TxFull as u32) {
+            return Err(());
+        }
+
+        self.registers.DR.write(byte as u32);
+        Ok(())
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.try_write_byte(byte).is_err() {}
+    }
+
+    pub fn has_byte(&self) -> bool {
+        !self.registers.FR.has_mask(FrFlags::RxEmpty as u32)
+    }
+
+    /// Waits for a byte to be received for at most the read timeout.
+    /// Returns `Err(())` if the timeout elapses first.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        timeout::wait_until(|| self.has_byte(), self.timeouts.read)
+    }
+
+    /// Reads a byte, blocking until one is received regardless of the read
+    /// timeout.
+    pub fn read_byte(&mut self) -> u8 {
+        timeout::read_byte_until(self, None).unwrap()
+    }
+
+    /// Reads a byte, waiting at most `timeout` for it. Returns `Err(())` if
+    /// none is received in time.
+    pub fn read_byte_timeout(&mut self, timeout: Duration) -> Result<u8, ()> {
+        let deadline = current_time() + timeout::micros(timeout);
+        timeout::read_byte_until(self, Some(deadline)).ok_or(())
+    }
+}
+
+impl TryRead for PL011 {
+    fn try_read_byte(&mut self) -> Option<u8> { PL011::try_read_byte(self) }
+}
+
+impl fmt::Write for PL011 {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.as_bytes() {
+            if *c == b'\n' {
+                self.write_byte(b'\r');
+            }
+
+            self.write_byte(*c);
+        }
+
+        Ok(())
+    }
+}
+
+impl io::Write for PL011 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for b in buf {
+            self.write_byte(*b);
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Waits until every queued byte has been sent.
+    fn flush(&mut self) -> io::Result<()> {
+        while self.registers.FR.has_mask(FrFlags::Busy as u32) {}
+
+        Ok(())
+    }
+}
+
+impl io::Read for PL011 {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeouts = self.timeouts;
+        timeout::read(self, timeouts, buf)
+    }
+}
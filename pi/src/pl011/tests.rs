@@ -0,0 +1,28 @@
+use super::*;
+
+/// The PL011 reference clock set by the firmware, in Hz.
+const CLOCK: u32 = 48_000_000;
+
+#[test]
+fn divisors_for_common_baud_rates() {
+    assert_eq!(divisors(CLOCK, 115200), Some((26, 3)));
+    assert_eq!(divisors(CLOCK, 9600), Some((312, 32)));
+    assert_eq!(divisors(3_000_000, 115200), Some((1, 40)));
+}
+
+#[test]
+fn divisors_round_to_nearest() {
+    // 1562.5 sixty-fourths rounds up to 1563.
+    assert_eq!(divisors(1_000_000, 2560), Some((24, 27)));
+    // 127.5 sixty-fourths rounds up, carrying into the integer part.
+    assert_eq!(divisors(255, 8), Some((2, 0)));
+}
+
+#[test]
+fn divisors_reject_unreachable_rates() {
+    assert_eq!(divisors(CLOCK, 0), None);
+    assert_eq!(divisors(CLOCK, 3_000_000), Some((1, 0)));
+    assert_eq!(divisors(CLOCK, 3_100_000), None);
+    assert_eq!(divisors(CLOCK, 46), Some((65217, 25)));
+    assert_eq!(divisors(CLOCK, 45), None);
+}
@@ -0,0 +1,123 @@
+//! Read timeouts shared by the UART drivers. Durations are kept in
+//! microseconds, as measured by `timer::current_time`.
+
+use std::io;
+use std::time::Duration;
+
+use timer::current_time;
+
+/// How long reads from a UART wait for data.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub(crate) struct Timeouts {
+    /// The longest a read waits in total, for its first byte and for the
+    /// rest of the buffer. `None` waits for the first byte forever.
+    pub read:       Option<u64>,
+    /// The longest a read waits for each byte after the first before
+    /// returning what it has. `None` returns as soon as no more bytes have
+    /// been received.
+    pub inter_byte: Option<u64>,
+}
+
+/// Returns `duration` in whole microseconds, saturating at `u64::MAX`.
+pub(crate) fn micros(duration: Duration) -> u64 {
+    let micros = duration.subsec_nanos() as u64 / 1_000;
+    duration
+        .as_secs()
+        .saturating_mul(1_000_000)
+        .saturating_add(micros)
+}
+
+/// A device that bytes can be read from without blocking.
+pub(crate) trait TryRead {
+    /// Reads a byte if one has been received.
+    fn try_read_byte(&mut self) -> Option<u8>;
+}
+
+/// Returns `true` if the time `deadline` has passed. The time is read even
+/// without a deadline so that waits poll the timer at a steady rate.
+fn expired(deadline: Option<u64>) -> bool {
+    let now = current_time();
+    deadline.map_or(false, |deadline| now >= deadline)
+}
+
+/// Returns `Ok(())` once `ready` returns `true`, or `Err(())` if `timeout`
+/// microseconds pass first.
+pub(crate) fn wait_until<F: Fn() -> bool>(
+    ready: F,
+    timeout: Option<u64>,
+) -> Result<(), ()> {
+    let deadline = timeout.map(|timeout| current_time() + timeout);
+    loop {
+        if ready() {
+            return Ok(());
+        }
+
+        if expired(deadline) {
+            return Err(());
+        }
+    }
+}
+
+/// Reads a byte, waiting until the time `deadline` at the latest.
+pub(crate) fn read_byte_until<T: TryRead>(
+    device: &mut T,
+    deadline: Option<u64>,
+) -> Option<u8> {
+    loop {
+        if let Some(byte) = device.try_read_byte() {
+            return Some(byte);
+        }
+
+        if expired(deadline) {
+            return None;
+        }
+    }
+}
+
+/// Fills `buf` from `device` as governed by `timeouts`, returning the number
+/// of bytes read. Fails with `TimedOut` if no byte arrives before the read
+/// timeout.
+pub(crate) fn read<T: TryRead>(
+    device: &mut T,
+    timeouts: Timeouts,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
+    let deadline = timeouts.read.map(|timeout| current_time() + timeout);
+    match read_byte_until(device, deadline) {
+        Some(byte) => buf[0] = byte,
+        None => {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Timeout"))
+        }
+    }
+
+    let mut read = 1;
+    while read < buf.len() {
+        let now = current_time();
+        if deadline.map_or(false, |deadline| now >= deadline) {
+            break;
+        }
+
+        let byte = match timeouts.inter_byte {
+            Some(gap) => {
+                let next = match deadline {
+                    Some(deadline) if deadline < now + gap => deadline,
+                    _ => now + gap,
+                };
+                read_byte_until(device, Some(next))
+            }
+            None => device.try_read_byte(),
+        };
+
+        match byte {
+            Some(byte) => buf[read] = byte,
+            None => break,
+        }
+        read += 1;
+    }
+
+    Ok(read)
+}
@@ -1,3 +1,6 @@
+// Host-side tests read the mock clock instead of the system timer.
+#![cfg_attr(test, allow(dead_code, unused_imports))]
+
 use common::IO_BASE;
 use volatile::{ReadVolatile, Reserved};
 use volatile::prelude::*;
@@ -11,6 +14,11 @@ struct TimerRegisters {
     chi:   ReadVolatile<u32>,
 }
 
+#[cfg(test)]
+pub use self::mock::current_time;
+
+/// Returns the number of microseconds since the system timer started.
+#[cfg(not(test))]
 pub fn current_time() -> u64 {
     let registers = unsafe { &*(TIMER_BASE as *const TimerRegisters) };
     (registers.chi.read() as u64) << 32 | registers.clo.read() as u64
@@ -23,3 +31,43 @@ pub fn spin_sleep_us(us: u64) {
 }
 
 pub fn spin_sleep_ms(ms: u64) { spin_sleep_us(ms * 1000); }
+
+/// A clock standing in for the system timer in host-side tests. Each thread
+/// has its own clock, which starts at `0` and advances by one microsecond
+/// every time it is read.
+#[cfg(test)]
+pub mod mock {
+    use std::cell::{Cell, RefCell};
+
+    thread_local! {
+        static NOW: Cell<u64> = Cell::new(0);
+        static ON_TICK: RefCell<Option<Box<Fn(u64)>>> = RefCell::new(None);
+    }
+
+    /// Returns the current mocked time, then advances it, after calling the
+    /// function set with `on_tick` with the time returned.
+    pub fn current_time() -> u64 {
+        let now = NOW.with(|clock| {
+            let now = clock.get();
+            clock.set(now + 1);
+            now
+        });
+
+        ON_TICK.with(|on_tick| {
+            if let Some(ref f) = *on_tick.borrow() {
+                f(now);
+            }
+        });
+
+        now
+    }
+
+    /// Returns the current mocked time without advancing it.
+    pub fn now() -> u64 { NOW.with(|clock| clock.get()) }
+
+    /// Calls `f` with the time whenever the clock is read, e.g. to change
+    /// mocked registers as time passes.
+    pub fn on_tick<F: Fn(u64) + 'static>(f: F) {
+        ON_TICK.with(|on_tick| *on_tick.borrow_mut() = Some(Box::new(f)));
+    }
+}
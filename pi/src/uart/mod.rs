@@ -0,0 +1,553 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use common::IO_BASE;
+use gpio::{Alt, ClaimError, Function, GPIO};
+use timer::current_time;
+use timeout::{self, Timeouts, TryRead};
+use volatile::{ReadVolatile, Volatile};
+use volatile::prelude::*;
+
+/// The `AUXENB` register from page 9 of the BCM2837 documentation.
+const AUX_ENABLES: *mut Volatile<u8> =
+    (IO_BASE + 0x215004) as *mut Volatile<u8>;
+
+const MU_BASE: usize = IO_BASE + 0x00215040;
+
+/// The name the mini UART claims its pins with.
+const OWNER: &str = "mini uart";
+
+#[cfg(test)]
+mod tests;
+
+#[repr(C)]
+struct Registers {
+    io:      Volatile<u32>, // write/read to an from UART FIFOs
+    ier:     Volatile<u32>, // interrupt enable
+    iir:     Volatile<u32>, // interrupt statu
+    lcr:     Volatile<u32>, // line data control
+    mcr:     Volatile<u32>, // modem control
+    lsr:     ReadVolatile<u32>, // line data status
+    msr:     ReadVolatile<u32>, // modem status
+    scratch: Volatile<u32>,
+    cntl:    Volatile<u32>,     // various extra features
+    stat:    ReadVolatile<u32>, // extra internal status information
+    baud:    Volatile<u16>,     // direct access to the 16-bit baudrate counter
+}
+
+/// The capacity, in bytes, of each of the receive and transmit buffers used
+/// while interrupts are enabled.
+pub const BUFFER_SIZE: usize = 256;
+
+/// The depth of the mini UART's receive and transmit FIFOs.
+const FIFO_DEPTH: usize = 8;
+
+/// A fixed-size FIFO of bytes.
+struct RingBuffer {
+    data: [u8; BUFFER_SIZE],
+    head: usize,
+    len:  usize,
+}
+
+impl RingBuffer {
+    fn new() -> RingBuffer {
+        RingBuffer {
+            data: [0; BUFFER_SIZE],
+            head: 0,
+            len:  0,
+        }
+    }
+
+    fn is_empty(&self) -> bool { self.len == 0 }
+
+    fn is_full(&self) -> bool { self.len == BUFFER_SIZE }
+
+    /// Appends `byte`, returning `Err(())` if the buffer is full.
+    fn push(&mut self, byte: u8) -> Result<(), ()> {
+        if self.is_full() {
+            return Err(());
+        }
+
+        self.data[(self.head + self.len) % BUFFER_SIZE] = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the oldest byte, if any.
+    fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % BUFFER_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// Counts of received bytes that were lost while interrupts were enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Overruns {
+    /// Times the hardware receive FIFO overflowed before it was drained.
+    pub fifo:   usize,
+    /// Bytes dropped because the receive buffer was full.
+    pub buffer: usize,
+}
+
+pub struct UART {
+    registers:  &'static mut Registers,
+    clock:      u32,
+    timeouts:   Timeouts,
+    interrupts: bool,
+    rx:         RingBuffer,
+    tx:         RingBuffer,
+    overruns:   Overruns,
+    /// The pins claimed for the UART, released when it is dropped.
+    _pins:      [Option<GPIO<Alt>>; 4],
+}
+
+#[repr(u8)]
+enum LsrStatus {
+    DataReady = 1,
+    RxOverrun = 1 << 1,
+    TxAvailable = 1 << 5,
+}
+
+/// Bits of the `ier` register. The BCM2837 documentation has the receive and
+/// transmit bits swapped, and bits 3:2 must be set for the mini UART to raise
+/// interrupts at all.
+#[repr(u8)]
+enum IerFlags {
+    Rx = 1,
+    Tx = 1 << 1,
+    Required = 0b11 << 2,
+}
+
+/// Writing this to the `iir` register clears both FIFOs.
+const IIR_CLEAR_FIFOS: u32 = 0b110;
+
+/// Setting this bit in the `lcr` register pulls the TX line low.
+const LCR_BREAK: u32 = 1 << 6;
+
+/// Bits of the `cntl` register.
+#[repr(u8)]
+enum CntlFlags {
+    RxEnable = 1,
+    TxEnable = 1 << 1,
+    RtsAuto = 1 << 2,
+    CtsAuto = 1 << 3,
+}
+
+/// The `lsr` bit set once the transmit FIFO is empty and the last byte has
+/// been shifted out.
+const LSR_TX_IDLE: u32 = 1 << 6;
+
+/// The number of data bits in a character. The mini UART has no parity and
+/// always sends one stop bit.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DataBits {
+    Seven = 0b00,
+    Eight = 0b11,
+}
+
+/// Line settings for a `UART`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct UARTConfig {
+    /// The frequency of the VPU core clock in Hz, which the mini UART's baud
+    /// rate is derived from. The firmware runs it at 250 MHz unless
+    /// `core_freq` is given in `config.txt`.
+    pub clock:        u32,
+    pub baud_rate:    u32,
+    pub data_bits:    DataBits,
+    /// Whether the UART controls RTS (GPIO 17) and honors CTS (GPIO 16),
+    /// pausing the sender when its receive FIFO is almost full and pausing
+    /// itself while CTS is deasserted.
+    pub flow_control: bool,
+}
+
+impl Default for UARTConfig {
+    /// 115200 baud with 8 data bits from a 250 MHz clock, without flow
+    /// control.
+    fn default() -> UARTConfig {
+        UARTConfig {
+            clock:        250_000_000,
+            baud_rate:    115200,
+            data_bits:    DataBits::Eight,
+            flow_control: false,
+        }
+    }
+}
+
+impl UARTConfig {
+    /// Returns the value of the baud rate register closest to `baud_rate`,
+    /// or `None` if the rate is out of the register's range for `clock`.
+    /// The resulting rate is `clock / (8 * (divisor + 1))`.
+    pub fn divisor(&self) -> Option<u16> {
+        let step = 8 * self.baud_rate as u64;
+        if step == 0 {
+            return None;
+        }
+
+        let divisor = (self.clock as u64 + step / 2) / step;
+        if divisor == 0 || divisor > 0x10000 {
+            return None;
+        }
+
+        Some((divisor - 1) as u16)
+    }
+}
+
+impl UART {
+    /// Returns a new `UART` with 8 data bits whose baud rate register is set
+    /// to `divisor` directly. `270` is 115200 baud with the default clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if pin 14 or 15 is owned by another driver.
+    pub fn new(divisor: u16) -> UART {
+        match UART::init(divisor, UARTConfig::default()) {
+            Ok(uart) => uart,
+            Err(e) => panic!("UART::new(): {}", e),
+        }
+    }
+
+    /// Returns a new `UART` configured with `config`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.baud_rate` cannot be reached from `config.clock`,
+    /// i.e. if `config.divisor()` is `None`, or if the UART's pins are owned
+    /// by another driver.
+    pub fn new_with_config(config: UARTConfig) -> UART {
+        match UART::claim(config) {
+            Ok(uart) => uart,
+            Err(e) => panic!("UART::new_with_config(): {}", e),
+        }
+    }
+
+    /// Returns a new `UART` configured with `config`, claiming its pins for
+    /// the mini UART.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `ClaimError` for the first of the UART's pins that is
+    /// owned by another driver. No pin is claimed or changed in that case.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.baud_rate` cannot be reached from `config.clock`,
+    /// i.e. if `config.divisor()` is `None`.
+    pub fn claim(config: UARTConfig) -> Result<UART, ClaimError> {
+        match config.divisor() {
+            Some(divisor) => UART::init(divisor, config),
+            None => panic!(
+                "UART::claim(): baud rate {} unreachable from a {} Hz clock",
+                config.baud_rate, config.clock
+            ),
+        }
+    }
+
+    fn init(divisor: u16, config: UARTConfig) -> Result<UART, ClaimError> {
+        // Claim every pin before switching any of them to the UART.
+        let tx = GPIO::claim(14, OWNER)?;
+        let rx = GPIO::claim(15, OWNER)?;
+        let flow = if config.flow_control {
+            Some((GPIO::claim(16, OWNER)?, GPIO::claim(17, OWNER)?))
+        } else {
+            None
+        };
+
+        // set pins 14 and 15 to TXD1/RXD1
+        let mut pins = [
+            Some(tx.into_alt(Function::Alt5)),
+            Some(rx.into_alt(Function::Alt5)),
+            None,
+            None,
+        ];
+
+        if let Some((cts, rts)) = flow {
+            // set pins 16 and 17 to CTS1/RTS1
+            pins[2] = Some(cts.into_alt(Function::Alt5));
+            pins[3] = Some(rts.into_alt(Function::Alt5));
+        }
+
+        let registers = unsafe {
+            // enable mini uart
+            (*AUX_ENABLES).or_mask(0b1);
+            &mut *(MU_BASE as *mut Registers)
+        };
+
+        let mut uart = UART::configure(registers, divisor, config);
+        uart._pins = pins;
+        Ok(uart)
+    }
+
+    /// Returns a `UART` driving `registers` after setting their line
+    /// settings and baud rate register from `config` and `divisor`.
+    fn configure(
+        registers: &'static mut Registers,
+        divisor: u16,
+        config: UARTConfig,
+    ) -> UART {
+        let mut cntl = CntlFlags::RxEnable as u32 | CntlFlags::TxEnable as u32;
+        if config.flow_control {
+            cntl |= CntlFlags::RtsAuto as u32 | CntlFlags::CtsAuto as u32;
+        }
+
+        // Set the data size
+        registers.lcr.write(config.data_bits as u32);
+        // Set baud rate to the divisor given
+        registers.baud.write(divisor);
+        // Enable tx and rx
+        registers.cntl.write(cntl);
+
+        UART::from_registers(registers, config.clock)
+    }
+
+    /// Returns a `UART` driving the already configured `registers`, whose
+    /// baud rate is derived from a `clock` Hz clock.
+    fn from_registers(registers: &'static mut Registers, clock: u32) -> UART {
+        UART {
+            registers,
+            clock,
+            timeouts: Timeouts::default(),
+            interrupts: false,
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            overruns: Overruns::default(),
+            _pins: [None, None, None, None],
+        }
+    }
+
+    /// Returns the baud rate the UART is running at, which may differ
+    /// slightly from the one requested.
+    pub fn baud_rate(&self) -> u32 {
+        self.clock / (8 * (self.registers.baud.read() as u32 + 1))
+    }
+
+    /// Starts (`true`) or stops (`false`) sending a break by holding the TX
+    /// line low. Bytes already written are sent before the break starts.
+    pub fn set_break(&mut self, enable: bool) {
+        if enable {
+            let _ = io::Write::flush(self);
+            while !self.registers.lsr.has_mask(LSR_TX_IDLE) {}
+            self.registers.lcr.or_mask(LCR_BREAK);
+        } else {
+            self.registers.lcr.and_mask(!LCR_BREAK);
+        }
+    }
+
+    /// Sets how long reads wait in total: for the first byte in
+    /// `wait_for_byte` and `io::Read::read`, and for the rest of the buffer
+    /// in the latter. `None`, the default, waits forever.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeouts.read = timeout.map(timeout::micros);
+    }
+
+    /// Sets how long `io::Read::read` waits for each byte after the first
+    /// before returning the bytes it has. `None`, the default, returns as
+    /// soon as no more bytes have been received.
+    pub fn set_inter_byte_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeouts.inter_byte = timeout.map(timeout::micros);
+    }
+
+    /// Switches to interrupt-driven mode. Received bytes are moved into a
+    /// receive buffer and queued bytes are sent from a transmit buffer by
+    /// `handle_interrupt`, which the kernel's IRQ handler should call when
+    /// `Interrupt::Aux` is pending. Both buffers hold `BUFFER_SIZE` bytes.
+    ///
+    /// The buffers are also serviced whenever bytes are read or written, so
+    /// no bytes are lost if the interrupt is not routed to the CPU.
+    pub fn enable_interrupts(&mut self) {
+        if !self.interrupts {
+            self.registers.iir.write(IIR_CLEAR_FIFOS);
+            self.interrupts = true;
+            self.update_interrupts();
+        }
+    }
+
+    /// Returns to polled mode, waiting for queued bytes to be sent first.
+    /// Bytes already received remain readable.
+    pub fn disable_interrupts(&mut self) {
+        if self.interrupts {
+            let _ = io::Write::flush(self);
+            self.registers.ier.write(0);
+            self.interrupts = false;
+        }
+    }
+
+    /// Returns the number of received bytes lost so far in interrupt-driven
+    /// mode.
+    pub fn overruns(&self) -> Overruns { self.overruns }
+
+    /// Services the mini UART interrupt: drains the receive FIFO into the
+    /// receive buffer and refills the transmit FIFO from the transmit
+    /// buffer. Must be called with the mini UART interrupt masked, as it is
+    /// in an IRQ handler.
+    pub fn handle_interrupt(&mut self) {
+        self.service();
+        self.update_interrupts();
+    }
+
+    /// Moves bytes between the FIFOs and the ring buffers, at most a FIFO's
+    /// worth in each direction.
+    fn service(&mut self) {
+        for _ in 0..FIFO_DEPTH {
+            let status = self.registers.lsr.read();
+            if status & LsrStatus::RxOverrun as u32 != 0 {
+                self.overruns.fifo += 1;
+            }
+
+            let mut progress = false;
+            if status & LsrStatus::DataReady as u32 != 0 {
+                let byte = self.registers.io.read() as u8;
+                if self.rx.push(byte).is_err() {
+                    self.overruns.buffer += 1;
+                }
+
+                progress = true;
+            }
+
+            if status & LsrStatus::TxAvailable as u32 != 0 {
+                if let Some(byte) = self.tx.pop() {
+                    self.registers.io.write(byte as u32);
+                    progress = true;
+                }
+            }
+
+            if !progress {
+                return;
+            }
+        }
+    }
+
+    /// Enables the receive interrupt, and the transmit interrupt while
+    /// there are bytes waiting to be sent.
+    fn update_interrupts(&mut self) {
+        let mut flags = IerFlags::Required as u32 | IerFlags::Rx as u32;
+        if !self.tx.is_empty() {
+            flags |= IerFlags::Tx as u32;
+        }
+
+        self.registers.ier.write(flags);
+    }
+
+    /// Runs `f` with the mini UART interrupt masked so that it cannot race
+    /// with `handle_interrupt` over the buffers.
+    fn masked<R, F: FnOnce(&mut UART) -> R>(&mut self, f: F) -> R {
+        self.registers.ier.write(0);
+        let result = f(self);
+        self.service();
+        self.update_interrupts();
+        result
+    }
+
+    /// Reads a byte if one has been received, without blocking.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        if self.interrupts {
+            return self.masked(|uart| {
+                uart.service();
+                uart.rx.pop()
+            });
+        }
+
+        if let Some(byte) = self.rx.pop() {
+            return Some(byte);
+        }
+
+        if self.registers.lsr.has_mask(LsrStatus::DataReady as u32) {
+            Some(self.registers.io.read() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Writes `byte` if there is room for it in the transmit FIFO, or in the
+    /// transmit buffer in interrupt-driven mode, without blocking. Returns
+    /// `Err(())` if there is no room.
+    pub fn try_write_byte(&mut self, byte: u8) -> Result<(), ()> {
+        if self.interrupts {
+            return self.masked(|uart| uart.tx.push(byte));
+        }
+
+        if self.registers.lsr.has_mask(LsrStatus::TxAvailable as u32) {
+            self.registers.io.write(byte as u32);
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.try_write_byte(byte).is_err() {}
+    }
+
+    pub fn has_byte(&self) -> bool {
+        !self.rx.is_empty()
+            || self.registers.lsr.has_mask(LsrStatus::DataReady as u32)
+    }
+
+    /// Waits for a byte to be received for at most the read timeout.
+    /// Returns `Err(())` if the timeout elapses first.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        timeout::wait_until(|| self.has_byte(), self.timeouts.read)
+    }
+
+    /// Reads a byte, blocking until one is received regardless of the read
+    /// timeout.
+    pub fn read_byte(&mut self) -> u8 {
+        timeout::read_byte_until(self, None).unwrap()
+    }
+
+    /// Reads a byte, waiting at most `timeout` for it. Returns `Err(())` if
+    /// none is received in time.
+    pub fn read_byte_timeout(&mut self, timeout: Duration) -> Result<u8, ()> {
+        let deadline = current_time() + timeout::micros(timeout);
+        timeout::read_byte_until(self, Some(deadline)).ok_or(())
+    }
+}
+
+impl TryRead for UART {
+    fn try_read_byte(&mut self) -> Option<u8> { UART::try_read_byte(self) }
+}
+
+impl fmt::Write for UART {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.as_bytes() {
+            if *c == b'\n' {
+                self.write_byte(b'\r');
+            }
+
+            self.write_byte(*c);
+        }
+
+        Ok(())
+    }
+}
+
+impl io::Write for UART {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for b in buf {
+            self.write_byte(*b);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while self.interrupts && !self.tx.is_empty() {
+            self.masked(|_| ());
+        }
+
+        Ok(())
+    }
+}
+
+impl io::Read for UART {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeouts = self.timeouts;
+        timeout::read(self, timeouts, buf)
+    }
+}
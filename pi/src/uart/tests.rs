@@ -0,0 +1,387 @@
+use std::io::{ErrorKind, Read};
+use std::mem;
+use std::ptr;
+
+use super::*;
+use timer::mock;
+
+/// The line side of a mocked register block.
+#[derive(Clone, Copy)]
+struct Line {
+    io:  usize,
+    ier: usize,
+    lsr: usize,
+}
+
+impl Line {
+    /// Makes `byte` available to the UART until `idle` is called.
+    fn receive(&self, byte: u8) {
+        unsafe {
+            ptr::write_volatile(self.io as *mut u32, byte as u32);
+            let ready = LsrStatus::DataReady as u32;
+            ptr::write_volatile(self.lsr as *mut u32, ready);
+        }
+    }
+
+    /// Stops making bytes available.
+    fn idle(&self) {
+        unsafe { ptr::write_volatile(self.lsr as *mut u32, 0) }
+    }
+
+    /// Empties the transmit FIFO, with no bytes available.
+    fn transmit_ready(&self) {
+        let idle = LsrStatus::TxAvailable as u32 | LSR_TX_IDLE;
+        unsafe { ptr::write_volatile(self.lsr as *mut u32, idle) }
+    }
+
+    /// Returns the last byte the UART wrote to its FIFO.
+    fn transmitted(&self) -> u8 {
+        unsafe { ptr::read_volatile(self.io as *const u32) as u8 }
+    }
+
+    /// Returns the interrupts the UART has enabled.
+    fn interrupts(&self) -> u32 {
+        unsafe { ptr::read_volatile(self.ier as *const u32) }
+    }
+}
+
+/// Returns a zeroed register block in memory.
+fn mock_registers() -> &'static mut Registers {
+    unsafe { &mut *Box::into_raw(Box::new(mem::zeroed())) }
+}
+
+/// Returns a `UART` backed by a zeroed register block in memory and the
+/// `Line` that feeds it.
+fn mock_uart() -> (UART, Line) {
+    let registers = mock_registers();
+    let line = Line {
+        io:  &mut registers.io as *mut _ as usize,
+        ier: &mut registers.ier as *mut _ as usize,
+        lsr: &mut registers.lsr as *mut _ as usize,
+    };
+
+    (UART::from_registers(registers, 250_000_000), line)
+}
+
+/// Returns a `Duration` of `n` microseconds.
+fn micros(n: u32) -> Duration {
+    Duration::new(0, n * 1_000)
+}
+
+/// Makes `byte` available between the times `from` and `until`.
+fn receive_between(line: Line, byte: u8, from: u64, until: u64) {
+    mock::on_tick(move |now| {
+        if now >= from && now < until {
+            line.receive(byte);
+        } else {
+            line.idle();
+        }
+    });
+}
+
+#[test]
+fn wait_for_byte_times_out() {
+    let (mut uart, _) = mock_uart();
+    uart.set_read_timeout(Some(micros(100)));
+
+    let start = mock::now();
+    assert_eq!(uart.wait_for_byte(), Err(()));
+
+    let elapsed = mock::now() - start;
+    assert!(elapsed >= 100 && elapsed <= 102, "waited {}us", elapsed);
+}
+
+#[test]
+fn wait_for_byte_succeeds_within_timeout() {
+    let (mut uart, line) = mock_uart();
+    uart.set_read_timeout(Some(micros(100)));
+    receive_between(line, b'a', 50, 1000);
+
+    assert_eq!(uart.wait_for_byte(), Ok(()));
+    assert!(mock::now() < 100);
+}
+
+#[test]
+fn cleared_read_timeout_waits_forever() {
+    let (mut uart, line) = mock_uart();
+    uart.set_read_timeout(Some(micros(10)));
+    uart.set_read_timeout(None);
+    receive_between(line, b'a', 5000, 6000);
+
+    assert_eq!(uart.wait_for_byte(), Ok(()));
+    assert!(mock::now() > 5000);
+}
+
+#[test]
+fn read_byte_timeout() {
+    let (mut uart, line) = mock_uart();
+
+    let start = mock::now();
+    assert_eq!(uart.read_byte_timeout(micros(50)), Err(()));
+    let elapsed = mock::now() - start;
+    assert!(elapsed >= 50 && elapsed <= 52, "waited {}us", elapsed);
+
+    receive_between(line, b'x', 0, 1000);
+    assert_eq!(uart.read_byte_timeout(micros(50)), Ok(b'x'));
+}
+
+#[test]
+fn read_byte_ignores_read_timeout() {
+    let (mut uart, line) = mock_uart();
+    uart.set_read_timeout(Some(micros(10)));
+    receive_between(line, b'z', 200, 300);
+
+    assert_eq!(uart.read_byte(), b'z');
+    assert!(mock::now() > 200);
+}
+
+#[test]
+fn read_times_out_without_data() {
+    let (mut uart, _) = mock_uart();
+    uart.set_read_timeout(Some(micros(100)));
+
+    let mut buf = [0; 8];
+    let error = uart.read(&mut buf).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+    assert!(mock::now() >= 100);
+}
+
+#[test]
+fn read_fills_buffer() {
+    let (mut uart, line) = mock_uart();
+    line.receive(b'a');
+
+    let mut buf = [0; 4];
+    assert_eq!(uart.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf, b"aaaa");
+}
+
+#[test]
+fn read_without_inter_byte_timeout_returns_when_idle() {
+    let (mut uart, line) = mock_uart();
+    receive_between(line, b'a', 0, 10);
+    uart.set_read_timeout(Some(micros(1000)));
+
+    let mut buf = [0; 100];
+    let read = uart.read(&mut buf).unwrap();
+    assert!(read > 0 && read < 100, "read {} bytes", read);
+    assert!(buf[..read].iter().all(|&b| b == b'a'));
+    assert!(mock::now() < 20);
+}
+
+#[test]
+fn read_waits_inter_byte_timeout_for_more() {
+    let (mut uart, line) = mock_uart();
+    mock::on_tick(move |now| match now {
+        0...9 => line.receive(b'a'),
+        30...39 => line.receive(b'b'),
+        _ => line.idle(),
+    });
+    uart.set_inter_byte_timeout(Some(micros(50)));
+
+    let mut buf = [0; 100];
+    let read = uart.read(&mut buf).unwrap();
+    assert!(buf[..read].contains(&b'b'));
+    assert!(mock::now() >= 90, "returned at {}us", mock::now());
+}
+
+#[test]
+fn read_returns_after_inter_byte_timeout() {
+    let (mut uart, line) = mock_uart();
+    mock::on_tick(move |now| match now {
+        0...9 => line.receive(b'a'),
+        30...39 => line.receive(b'b'),
+        _ => line.idle(),
+    });
+    uart.set_inter_byte_timeout(Some(micros(5)));
+
+    let mut buf = [0; 100];
+    let read = uart.read(&mut buf).unwrap();
+    assert!(!buf[..read].contains(&b'b'));
+    assert!(mock::now() < 30, "returned at {}us", mock::now());
+}
+
+#[test]
+fn read_stops_at_total_timeout() {
+    let (mut uart, line) = mock_uart();
+    line.receive(b'a');
+    uart.set_read_timeout(Some(micros(50)));
+    uart.set_inter_byte_timeout(Some(micros(20)));
+
+    let mut buf = [0; 1000];
+    let read = uart.read(&mut buf).unwrap();
+    assert!(read > 0 && read < 1000, "read {} bytes", read);
+    let now = mock::now();
+    assert!(now >= 50 && now <= 52, "returned at {}us", now);
+}
+
+#[test]
+fn ring_buffer_wraps_around() {
+    let mut buffer = RingBuffer::new();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.pop(), None);
+
+    for i in 0..BUFFER_SIZE {
+        assert_eq!(buffer.push(i as u8), Ok(()));
+    }
+    assert!(buffer.is_full());
+    assert_eq!(buffer.push(0), Err(()));
+
+    for i in 0..10 {
+        assert_eq!(buffer.pop(), Some(i as u8));
+    }
+    for i in BUFFER_SIZE..BUFFER_SIZE + 10 {
+        assert_eq!(buffer.push(i as u8), Ok(()));
+    }
+    assert!(buffer.is_full());
+
+    for i in 10..BUFFER_SIZE + 10 {
+        assert_eq!(buffer.pop(), Some(i as u8));
+    }
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.pop(), None);
+}
+
+#[test]
+fn try_read_byte_without_data() {
+    let (mut uart, _) = mock_uart();
+    assert_eq!(uart.try_read_byte(), None);
+
+    uart.enable_interrupts();
+    assert_eq!(uart.try_read_byte(), None);
+}
+
+#[test]
+fn try_write_byte_without_room() {
+    let (mut uart, _) = mock_uart();
+    assert_eq!(uart.try_write_byte(b'a'), Err(()));
+
+    uart.enable_interrupts();
+    for _ in 0..BUFFER_SIZE {
+        assert_eq!(uart.try_write_byte(b'a'), Ok(()));
+    }
+    assert_eq!(uart.try_write_byte(b'a'), Err(()));
+}
+
+#[test]
+fn full_receive_buffer_counts_overruns() {
+    let (mut uart, line) = mock_uart();
+    uart.enable_interrupts();
+    line.receive(b'a');
+
+    for _ in 0..BUFFER_SIZE / FIFO_DEPTH {
+        uart.handle_interrupt();
+    }
+    assert_eq!(uart.overruns(), Overruns::default());
+
+    uart.handle_interrupt();
+    assert_eq!(uart.overruns().buffer, FIFO_DEPTH);
+    assert_eq!(uart.overruns().fifo, 0);
+
+    line.idle();
+    assert_eq!(uart.try_read_byte(), Some(b'a'));
+}
+
+#[test]
+fn transmit_interrupt_follows_transmit_buffer() {
+    let (mut uart, line) = mock_uart();
+    let receive = IerFlags::Required as u32 | IerFlags::Rx as u32;
+    let transmit = receive | IerFlags::Tx as u32;
+
+    uart.enable_interrupts();
+    assert_eq!(line.interrupts(), receive);
+
+    assert_eq!(uart.try_write_byte(b'x'), Ok(()));
+    assert_eq!(line.interrupts(), transmit);
+
+    line.transmit_ready();
+    uart.handle_interrupt();
+    assert_eq!(line.transmitted(), b'x');
+    assert_eq!(line.interrupts(), receive);
+
+    uart.disable_interrupts();
+    assert_eq!(line.interrupts(), 0);
+}
+
+#[test]
+fn divisor_for_default_config() {
+    assert_eq!(UARTConfig::default().divisor(), Some(270));
+}
+
+#[test]
+fn divisor_rejects_unreachable_rates() {
+    let config = |baud_rate| UARTConfig {
+        baud_rate,
+        ..UARTConfig::default()
+    };
+
+    assert_eq!(config(0).divisor(), None);
+    // 31.25 Mbaud is the fastest rate, and rates up to twice that round to
+    // it.
+    assert_eq!(config(31_250_000).divisor(), Some(0));
+    assert_eq!(config(62_500_000).divisor(), Some(0));
+    assert_eq!(config(62_500_001).divisor(), None);
+    // 477 baud is the slowest rate that rounds to a divisor that fits.
+    assert_eq!(config(477).divisor(), Some(65513));
+    assert_eq!(config(476).divisor(), None);
+}
+
+#[test]
+fn baud_rate_reads_back_achieved_rate() {
+    let config = UARTConfig::default();
+    let uart = UART::configure(mock_registers(), 270, config);
+    assert_eq!(uart.baud_rate(), 115313);
+
+    let config = UARTConfig {
+        baud_rate: 9600,
+        ..config
+    };
+    let divisor = config.divisor().unwrap();
+    assert_eq!(divisor, 3254);
+    let uart = UART::configure(mock_registers(), divisor, config);
+    assert_eq!(uart.baud_rate(), 9600);
+}
+
+#[test]
+fn configure_writes_line_settings() {
+    let rx_tx = CntlFlags::RxEnable as u32 | CntlFlags::TxEnable as u32;
+    let flow = CntlFlags::RtsAuto as u32 | CntlFlags::CtsAuto as u32;
+
+    let uart = UART::configure(mock_registers(), 270, UARTConfig::default());
+    assert_eq!(uart.registers.lcr.read(), 0b11);
+    assert_eq!(uart.registers.baud.read(), 270);
+    assert_eq!(uart.registers.cntl.read(), rx_tx);
+
+    let config = UARTConfig {
+        data_bits: DataBits::Seven,
+        flow_control: true,
+        ..UARTConfig::default()
+    };
+    let uart = UART::configure(mock_registers(), 270, config);
+    assert_eq!(uart.registers.lcr.read(), 0b00);
+    assert_eq!(uart.registers.cntl.read(), rx_tx | flow);
+}
+
+#[test]
+fn set_break_holds_tx_low() {
+    let (mut uart, line) = mock_uart();
+    line.transmit_ready();
+    let data_bits = uart.registers.lcr.read();
+
+    uart.set_break(true);
+    assert_eq!(uart.registers.lcr.read(), data_bits | LCR_BREAK);
+
+    uart.set_break(false);
+    assert_eq!(uart.registers.lcr.read(), data_bits);
+}
+
+#[test]
+fn claim_fails_without_taking_pins() {
+    let owner = GPIO::claim(15, "test").unwrap();
+
+    let error = UART::claim(UARTConfig::default()).err();
+    assert_eq!(error, Some(ClaimError::Claimed(15, "test")));
+    assert!(GPIO::claim(14, "test").is_ok());
+
+    drop(owner);
+}
@@ -0,0 +1,85 @@
+//! A chain-loading bootloader, built in place of the kernel with the
+//! `bootloader` feature.
+//!
+//! Installed on the SD card once, the bootloader is loaded at
+//! `BOOTLOADER_START` (`kernel_address` in firmware/config.txt). It receives
+//! a kernel over the UART with XMODEM, e.g. from `ttywrite`, into
+//! `BINARY_START`, where the firmware would otherwise have loaded it, and
+//! jumps to it. If no kernel arrives, it falls back to the shell.
+
+use std::slice;
+use std::time::Duration;
+
+use pi::uart::{UARTConfig, UART};
+use xmodem::Xmodem;
+
+use console::kprintln;
+use shell::shell;
+
+/// The address the bootloader is loaded at and runs from.
+const BOOTLOADER_START: usize = 0x4000000;
+
+/// The address kernels are received at and started from.
+const BINARY_START: usize = 0x80000;
+
+/// The space below `BOOTLOADER_START` reserved for the bootloader's stack.
+const STACK_SIZE: usize = 0x100000;
+
+/// The size of the largest kernel that can be received.
+const MAX_BINARY_SIZE: usize = BOOTLOADER_START - STACK_SIZE - BINARY_START;
+
+/// How long to wait for each byte, in milliseconds. The receiver asks for
+/// the kernel again whenever this elapses.
+const READ_TIMEOUT_MS: u64 = 750;
+
+/// How many times to wait for a kernel before falling back to the shell.
+/// Each attempt lasts for `XmodemConfig::max_retries` read timeouts when
+/// nothing is sent.
+const RECEIVE_ATTEMPTS: usize = 4;
+
+/// Does nothing: unlike the kernel, the bootloader must not write to the
+/// UART before a kernel has been received, as it would corrupt the
+/// transfer.
+#[no_mangle]
+pub extern "C" fn mem_init(_binary_end: usize) {}
+
+#[no_mangle]
+pub extern "C" fn kmain() {
+    if let Ok(uart) = UART::claim(UARTConfig::default()) {
+        receive_kernel(uart);
+    }
+
+    kprintln!("No kernel received; starting the shell.");
+    shell("bootloader>");
+}
+
+/// Receives a kernel over `uart` and jumps to it. Returns, releasing the
+/// UART's pins for the console, if none arrives after `RECEIVE_ATTEMPTS`
+/// attempts.
+fn receive_kernel(mut uart: UART) {
+    uart.set_read_timeout(Some(Duration::from_millis(READ_TIMEOUT_MS)));
+
+    for _ in 0..RECEIVE_ATTEMPTS {
+        let kernel = unsafe {
+            slice::from_raw_parts_mut(BINARY_START as *mut u8, MAX_BINARY_SIZE)
+        };
+
+        if Xmodem::receive(&mut uart, kernel).is_ok() {
+            jump_to(BINARY_START as *mut u8);
+        }
+    }
+}
+
+/// Starts the code at `addr`.
+fn jump_to(addr: *mut u8) -> ! {
+    unsafe {
+        asm!("dsb sy
+              ic iallu
+              dsb sy
+              isb
+              br $0" : : "r"(addr as usize) : "memory" : "volatile");
+        loop {
+            asm!("wfe" :::: "volatile")
+        }
+    }
+}
@@ -2,51 +2,91 @@ use std::fmt;
 use std::io;
 
 use mutex::Mutex;
-use pi::uart::UART;
+use pi::gpio::ClaimError;
+#[cfg(feature = "pl011")]
+use pi::pl011::{PL011, PL011Config};
+#[cfg(not(feature = "pl011"))]
+use pi::uart::{UARTConfig, UART};
+
+/// The UART the console runs on: the PL011 with the `pl011` feature and the
+/// mini UART otherwise.
+#[cfg(feature = "pl011")]
+type Serial = PL011;
+#[cfg(not(feature = "pl011"))]
+type Serial = UART;
+
+/// Opens the console's UART at 115200 baud.
+#[cfg(feature = "pl011")]
+fn open() -> Result<Serial, ClaimError> {
+    PL011::claim(PL011Config::default())
+}
+#[cfg(not(feature = "pl011"))]
+fn open() -> Result<Serial, ClaimError> {
+    UART::claim(UARTConfig::default())
+}
 
 /// A global singleton allowing read/write access to the console.
 pub struct Console {
-    inner: Option<UART>,
+    inner: Option<Serial>,
 }
 
 impl Console {
     /// Creates a new instance of `Console`.
     const fn new() -> Console { Console { inner: None } }
 
-    /// Initializes the console if it's not already initialized.
+    /// Initializes the console if it's not already initialized. The console
+    /// stays uninitialized while the UART's pins are owned by another
+    /// driver.
     #[inline]
     fn initialize(&mut self) {
         match self.inner {
-            None => self.inner = Some(UART::new(270)),
+            None => self.inner = open().ok(),
             _ => {}
         }
     }
 
-    /// Returns a mutable borrow to the inner `UART`, initializing it as
-    /// needed.
-    fn inner(&mut self) -> &mut UART {
+    /// Returns a mutable borrow to the inner UART, initializing it as
+    /// needed, or `None` if it cannot be initialized.
+    fn inner(&mut self) -> Option<&mut Serial> {
         self.initialize();
 
-        self.inner.as_mut().unwrap()
+        self.inner.as_mut()
     }
 
     /// Reads a byte from the UART device, blocking until a byte is
     /// available.
-    pub fn read_byte(&mut self) -> u8 { self.inner().read_byte() }
+    pub fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Some(serial) = self.inner() {
+                return serial.read_byte();
+            }
+        }
+    }
 
-    /// Writes the byte `byte` to the UART device.
-    pub fn write_byte(&mut self, byte: u8) { self.inner().write_byte(byte); }
+    /// Writes the byte `byte` to the UART device. The byte is dropped if
+    /// the UART cannot be initialized.
+    pub fn write_byte(&mut self, byte: u8) {
+        if let Some(serial) = self.inner() {
+            serial.write_byte(byte);
+        }
+    }
 }
 
 impl io::Read for Console {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner().read(buf)
+        match self.inner() {
+            Some(serial) => serial.read(buf),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
     }
 }
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner().write(buf)
+        match self.inner() {
+            Some(serial) => serial.write(buf),
+            None => Ok(buf.len()),
+        }
     }
 
     fn flush(&mut self) -> io::Result<()> { Ok(()) }
@@ -54,7 +94,10 @@ impl io::Write for Console {
 
 impl fmt::Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.inner().write_str(s)
+        match self.inner() {
+            Some(serial) => serial.write_str(s),
+            None => Ok(()),
+        }
     }
 }
 
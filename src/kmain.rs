@@ -13,16 +13,24 @@ extern crate core;
 extern crate pi;
 extern crate stack_vec;
 extern crate volatile;
+#[cfg(feature = "bootloader")]
+extern crate xmodem;
 
 pub mod lang_items;
 pub mod mutex;
 pub mod console;
 pub mod shell;
+#[cfg(feature = "bootloader")]
+pub mod bootloader;
 
+#[cfg(not(feature = "bootloader"))]
 use console::kprintln;
+#[cfg(not(feature = "bootloader"))]
 use pi::atags::Atags;
+#[cfg(not(feature = "bootloader"))]
 use pi::timer::spin_sleep_ms;
 
+#[cfg(not(feature = "bootloader"))]
 #[no_mangle]
 pub extern "C" fn kmain() {
     for val in Atags::get() {
@@ -30,6 +38,7 @@ pub extern "C" fn kmain() {
     }
 }
 
+#[cfg(not(feature = "bootloader"))]
 #[no_mangle]
 pub extern "C" fn mem_init(binary_end: usize) {
     spin_sleep_ms(2000);
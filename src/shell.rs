@@ -1,4 +1,5 @@
 use console::{kprint, kprintln, CONSOLE};
+use pi::gpio::{self, Function};
 use stack_vec::StackVec;
 use std::str::from_utf8;
 
@@ -54,6 +55,7 @@ impl<'a> Command<'a> {
 
                 kprintln!("");
             }
+            path if path.as_bytes() == b"pins" => print_pins(),
             path => {
                 kprintln!("unknown command: {}", path);
             }
@@ -61,6 +63,27 @@ impl<'a> Command<'a> {
     }
 }
 
+/// Prints the function, level and owner of every GPIO pin that is claimed or
+/// not an input.
+fn print_pins() {
+    kprintln!("pin  function  level  owner");
+    for status in gpio::pins().iter() {
+        if status.owner.is_none() && status.function == Function::Input {
+            continue;
+        }
+
+        let level = if status.level { "high" } else { "low" };
+        let owner = status.owner.unwrap_or("-");
+        kprintln!(
+            "{:>3}  {:<8}  {:<5}  {}",
+            status.pin,
+            status.function,
+            level,
+            owner
+        );
+    }
+}
+
 /// Starts a shell using `prefix` as the prefix for each line. This function
 /// never returns: it is perpetually in a shell loop.
 pub fn shell(prefix: &str) -> ! {
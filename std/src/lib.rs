@@ -489,7 +489,7 @@ pub mod os;
 pub mod path;
 //- pub mod process;
 pub mod sync;
-//- pub mod time;
+pub mod time;
 //- pub mod heap;
 
 //- // Platform-abstraction modules
@@ -0,0 +1,262 @@
+//! A simulated Raspberry Pi for testing `ttywrite` without a board.
+//!
+//! `pisim` opens a pseudo-terminal and prints the path of its TTY. Like the
+//! Pi's bootloader, it then receives images sent to that TTY with XMODEM,
+//! writing each to a file and printing the serial settings the sender
+//! applied to the TTY.
+
+extern crate libc;
+extern crate structopt;
+#[macro_use]
+extern crate structopt_derive;
+extern crate xmodem;
+
+use std::ffi::{CStr, OsStr};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::symlink;
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::process;
+
+use structopt::StructOpt;
+use xmodem::{Progress, Xmodem, XmodemConfig};
+
+/// How long the receiver waits for a byte before sending another `NAK`, in
+/// milliseconds.
+const POLL_MS: libc::c_int = 1000;
+
+/// The baud rates `pisim` can report, by `termios` speed.
+const BAUD_RATES: &[(libc::speed_t, u32)] = &[
+    (libc::B1200, 1200),
+    (libc::B2400, 2400),
+    (libc::B4800, 4800),
+    (libc::B9600, 9600),
+    (libc::B19200, 19200),
+    (libc::B38400, 38400),
+    (libc::B57600, 57600),
+    (libc::B115200, 115200),
+    (libc::B230400, 230400),
+];
+
+#[derive(StructOpt, Debug)]
+#[structopt(about = "Simulate a Raspberry Pi receiving images with XMODEM.")]
+struct Opt {
+    #[structopt(short = "o", long = "output",
+                help = "Directory to write received images to",
+                default_value = ".", parse(from_os_str))]
+    output: PathBuf,
+
+    #[structopt(short = "n", long = "count", parse(try_from_str),
+                help = "Exit after receiving this many images")]
+    count: Option<usize>,
+
+    #[structopt(short = "l", long = "link",
+                help = "Create a symlink to the TTY at this path",
+                parse(from_os_str))]
+    link: Option<PathBuf>,
+}
+
+/// A pseudo-terminal, read and written through its master side. The slave
+/// side is kept open so that reads do not fail while no sender has the TTY
+/// open and so that the settings a sender applies persist.
+struct Pty {
+    master: RawFd,
+    slave:  RawFd,
+    path:   PathBuf,
+}
+
+impl Pty {
+    /// Opens a new pseudo-terminal whose TTY is in raw mode.
+    fn open() -> io::Result<Pty> {
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR) };
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut pty = Pty {
+            master,
+            slave: -1,
+            path: PathBuf::new(),
+        };
+
+        unsafe {
+            if libc::grantpt(master) < 0 || libc::unlockpt(master) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let name = libc::ptsname(master);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+
+            let name = CStr::from_ptr(name);
+            pty.path = PathBuf::from(OsStr::from_bytes(name.to_bytes()));
+            let flags = libc::O_RDWR | libc::O_NOCTTY;
+            pty.slave = libc::open(name.as_ptr(), flags);
+            if pty.slave < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Echoing the receiver's own `NAK`s back to it would corrupt the
+            // transfer until a sender sets up the TTY.
+            let mut termios = pty.termios()?;
+            libc::cfmakeraw(&mut termios);
+            if libc::tcsetattr(pty.slave, libc::TCSANOW, &termios) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(pty)
+    }
+
+    /// Returns the TTY's current settings.
+    fn termios(&self) -> io::Result<libc::termios> {
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            match libc::tcgetattr(self.slave, &mut termios) {
+                0 => Ok(termios),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+    }
+
+    /// Describes the TTY's current settings, as in `115200 baud, 8N1, flow
+    /// control none`.
+    fn settings(&self) -> io::Result<String> {
+        let termios = self.termios()?;
+        let speed = unsafe { libc::cfgetospeed(&termios) };
+        let baud = match BAUD_RATES.iter().find(|&&(s, _)| s == speed) {
+            Some(&(_, baud)) => baud.to_string(),
+            None => format!("unknown ({:#o})", speed),
+        };
+
+        let width = match termios.c_cflag & libc::CSIZE {
+            libc::CS5 => 5,
+            libc::CS6 => 6,
+            libc::CS7 => 7,
+            _ => 8,
+        };
+
+        let parity = match termios.c_cflag & (libc::PARENB | libc::PARODD) {
+            0 => 'N',
+            flags if flags & libc::PARODD != 0 => 'O',
+            _ => 'E',
+        };
+
+        let stop_bits = match termios.c_cflag & libc::CSTOPB {
+            0 => 1,
+            _ => 2,
+        };
+
+        let flow_control = if termios.c_cflag & libc::CRTSCTS != 0 {
+            "hardware"
+        } else if termios.c_iflag & (libc::IXON | libc::IXOFF) != 0 {
+            "software"
+        } else {
+            "none"
+        };
+
+        Ok(format!(
+            "{} baud, {}{}{}, flow control {}",
+            baud, width, parity, stop_bits, flow_control
+        ))
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.slave);
+            libc::close(self.master);
+        }
+    }
+}
+
+impl io::Read for Pty {
+    /// Reads from the TTY, failing with `TimedOut` if nothing arrives within
+    /// `POLL_MS` milliseconds.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut fd = libc::pollfd {
+            fd: self.master,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        match unsafe { libc::poll(&mut fd, 1, POLL_MS) } {
+            n if n < 0 => return Err(io::Error::last_os_error()),
+            0 => return Err(io::Error::new(io::ErrorKind::TimedOut, "idle")),
+            _ => {}
+        }
+
+        let ptr = buf.as_mut_ptr() as *mut libc::c_void;
+        match unsafe { libc::read(self.master, ptr, buf.len()) } {
+            n if n < 0 => Err(io::Error::last_os_error()),
+            n => Ok(n as usize),
+        }
+    }
+}
+
+impl io::Write for Pty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let ptr = buf.as_ptr() as *const libc::c_void;
+        match unsafe { libc::write(self.master, ptr, buf.len()) } {
+            n if n < 0 => Err(io::Error::last_os_error()),
+            n => Ok(n as usize),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if let Err(e) = run(&opt) {
+        eprintln!("pisim: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Receives images until `opt.count` have been received, if it is set.
+fn run(opt: &Opt) -> io::Result<()> {
+    let mut pty = Pty::open()?;
+    if let Some(ref link) = opt.link {
+        let _ = fs::remove_file(link);
+        symlink(&pty.path, link)?;
+    }
+
+    println!("pisim: listening on {}", pty.path.display());
+    io::stdout().flush()?;
+
+    let mut received = 0;
+    while opt.count.map_or(true, |count| received < count) {
+        let mut image = vec![];
+        let mut started = false;
+        let result = Xmodem::receive_with_config(
+            &mut pty,
+            &mut image,
+            XmodemConfig::default(),
+            |progress| started |= progress == Progress::Started,
+        );
+
+        match result {
+            Ok(n) => {
+                received += 1;
+                let name = format!("image-{}.bin", received);
+                File::create(opt.output.join(&name))?.write_all(&image)?;
+                let settings = pty.settings()?;
+                println!("pisim: received {} ({} bytes) at {}", name, n,
+                         settings);
+            }
+            // Nobody sent anything; keep waiting, as the bootloader would.
+            Err(_) if !started => continue,
+            Err(e) => println!("pisim: transfer failed: {}", e),
+        }
+
+        io::stdout().flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Discovery of serial devices that may be connected to a Pi.
+//!
+//! Candidates are the USB and on-board UART devices in `/dev`. On Linux, the
+//! USB vendor and product of each is read from sysfs.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests;
+
+/// Prefixes of the names of device files that may be serial adapters.
+const PREFIXES: &[&str] = &[
+    "ttyUSB", "ttyACM", "ttyAMA", "tty.usbserial", "tty.usbmodem",
+    "tty.SLAB_USBtoUART", "tty.wchusbserial",
+];
+
+/// How many directories above a TTY's sysfs device to look for its USB
+/// device.
+const MAX_USB_DEPTH: usize = 4;
+
+/// The USB device providing a serial port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usb {
+    pub vendor_id:    String,
+    pub product_id:   String,
+    pub manufacturer: Option<String>,
+    pub product:      Option<String>,
+    pub serial:       Option<String>,
+}
+
+/// A serial device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub path: PathBuf,
+    pub usb:  Option<Usb>,
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        let usb = match self.usb {
+            Some(ref usb) => usb,
+            None => return Ok(()),
+        };
+
+        write!(f, "  {}:{}", usb.vendor_id, usb.product_id)?;
+        if let Some(ref manufacturer) = usb.manufacturer {
+            write!(f, " {}", manufacturer)?;
+        }
+
+        if let Some(ref product) = usb.product {
+            write!(f, " {}", product)?;
+        }
+
+        match usb.serial {
+            Some(ref serial) => write!(f, " (serial {})", serial),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the serial devices in `/dev`, sorted by path.
+pub fn list() -> io::Result<Vec<Device>> {
+    list_in(Path::new("/dev"), Path::new("/sys/class/tty"))
+}
+
+/// Returns the serial devices in `dev`, sorted by path, looking up their USB
+/// devices in the sysfs TTY class directory `class`.
+fn list_in(dev: &Path, class: &Path) -> io::Result<Vec<Device>> {
+    let mut devices = vec![];
+    for entry in fs::read_dir(dev)? {
+        let name = entry?.file_name();
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        if PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+            devices.push(Device {
+                path: dev.join(name),
+                usb: usb_device(&class.join(name).join("device")),
+            });
+        }
+    }
+
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(devices)
+}
+
+/// Returns the USB device that the sysfs device `device`, usually a USB
+/// interface, belongs to.
+fn usb_device(device: &Path) -> Option<Usb> {
+    let mut dir = fs::canonicalize(device).ok()?;
+    for _ in 0..MAX_USB_DEPTH {
+        if let Some(vendor_id) = attribute(&dir, "idVendor") {
+            return Some(Usb {
+                vendor_id,
+                product_id: attribute(&dir, "idProduct")?,
+                manufacturer: attribute(&dir, "manufacturer"),
+                product: attribute(&dir, "product"),
+                serial: attribute(&dir, "serial"),
+            });
+        }
+
+        if !dir.pop() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Reads the sysfs attribute `name` of the device at `dir`.
+fn attribute(dir: &Path, name: &str) -> Option<String> {
+    let mut value = String::new();
+    File::open(dir.join(name))
+        .and_then(|mut file| file.read_to_string(&mut value))
+        .ok()?;
+
+    match value.trim() {
+        "" => None,
+        value => Some(value.to_string()),
+    }
+}
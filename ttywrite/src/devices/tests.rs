@@ -0,0 +1,96 @@
+use super::*;
+use std::env;
+use std::io::Write;
+use std::os::unix::fs::symlink;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A scratch directory, removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Scratch {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let unique = format!("ttywrite-{}-{}", name, now.subsec_nanos());
+        let dir = env::temp_dir().join(unique);
+        fs::create_dir_all(&dir).expect("scratch dir");
+        Scratch(dir)
+    }
+
+    fn file(&self, path: &str, contents: &str) {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).expect("parent dir");
+        let mut file = File::create(path).expect("create");
+        file.write_all(contents.as_bytes()).expect("write");
+    }
+
+    fn link(&self, link: &str, target: &str) {
+        let link = self.0.join(link);
+        fs::create_dir_all(link.parent().unwrap()).expect("parent dir");
+        symlink(self.0.join(target), link).expect("symlink");
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn test_list() {
+    let root = Scratch::new("list");
+    for name in &["ttyUSB1", "ttyUSB0", "ttyAMA0", "ttyS0", "tty1", "null"] {
+        root.file(&format!("dev/{}", name), "");
+    }
+
+    let usb = "sys/devices/usb1/1-1";
+    root.file(&format!("{}/idVendor", usb), "10c4\n");
+    root.file(&format!("{}/idProduct", usb), "ea60\n");
+    root.file(&format!("{}/manufacturer", usb), "Silicon Labs\n");
+    root.file(&format!("{}/product", usb), "CP2102 USB to UART\n");
+    root.file(&format!("{}/serial", usb), "\n");
+    root.file(&format!("{}/1-1:1.0/ttyUSB0/dev", usb), "188:0\n");
+    root.link("class/ttyUSB0/device", &format!("{}/1-1:1.0", usb));
+
+    let dev = root.0.join("dev");
+    let devices = list_in(&dev, &root.0.join("class")).expect("listed");
+    let paths: Vec<_> = devices.iter().map(|d| d.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![dev.join("ttyAMA0"), dev.join("ttyUSB0"), dev.join("ttyUSB1")]
+    );
+
+    assert_eq!(devices[0].usb, None);
+    assert_eq!(devices[2].usb, None);
+    assert_eq!(
+        devices[1].usb,
+        Some(Usb {
+            vendor_id: "10c4".to_string(),
+            product_id: "ea60".to_string(),
+            manufacturer: Some("Silicon Labs".to_string()),
+            product: Some("CP2102 USB to UART".to_string()),
+            serial: None,
+        })
+    );
+}
+
+#[test]
+fn test_display() {
+    let mut device = Device {
+        path: PathBuf::from("/dev/ttyUSB0"),
+        usb: None,
+    };
+    assert_eq!(device.to_string(), "/dev/ttyUSB0");
+
+    device.usb = Some(Usb {
+        vendor_id: "0403".to_string(),
+        product_id: "6001".to_string(),
+        manufacturer: Some("FTDI".to_string()),
+        product: None,
+        serial: Some("A50285BI".to_string()),
+    });
+    assert_eq!(
+        device.to_string(),
+        "/dev/ttyUSB0  0403:6001 FTDI (serial A50285BI)"
+    );
+}
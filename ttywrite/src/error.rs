@@ -0,0 +1,100 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use serial;
+
+/// The ways in which `ttywrite` can fail.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line or the profile configuration is invalid.
+    Usage(String),
+    /// The TTY device could not be opened.
+    Open(PathBuf, serial::Error),
+    /// The serial settings could not be read or applied.
+    Settings(serial::Error),
+    /// The device did not respond in time.
+    Timeout(io::Error),
+    /// The receiver cancelled the transfer.
+    Cancelled(io::Error),
+    /// A file could not be opened or created.
+    File(PathBuf, io::Error),
+    /// Any other I/O or protocol failure.
+    Io(io::Error),
+}
+
+impl Error {
+    /// Returns the process exit code for this error:
+    ///
+    ///   * `1`: the command line or the profiles are invalid
+    ///   * `2`: the TTY device could not be opened
+    ///   * `3`: the serial settings could not be applied
+    ///   * `4`: the device timed out
+    ///   * `5`: the receiver cancelled the transfer
+    ///   * `6`: any other I/O failure
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Usage(_) => 1,
+            Error::Open(..) => 2,
+            Error::Settings(_) => 3,
+            Error::Timeout(_) => 4,
+            Error::Cancelled(_) => 5,
+            Error::File(..) | Error::Io(_) => 6,
+        }
+    }
+
+    /// Returns a short, stable name for the kind of this error.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Error::Usage(_) => "usage",
+            Error::Open(..) => "open",
+            Error::Settings(_) => "settings",
+            Error::Timeout(_) => "timeout",
+            Error::Cancelled(_) => "cancelled",
+            Error::File(..) | Error::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(ref msg) => write!(f, "{}", msg),
+            Error::Open(ref path, ref e) => {
+                write!(f, "failed to open {}: {}", path.display(), e)
+            }
+            Error::Settings(ref e) => {
+                write!(f, "failed to apply serial settings: {}", e)
+            }
+            Error::Timeout(ref e) => {
+                write!(f, "timed out waiting for the device: {}", e)
+            }
+            Error::Cancelled(ref e) => {
+                write!(f, "the receiver cancelled the transfer: {}", e)
+            }
+            Error::File(ref path, ref e) => {
+                write!(f, "failed to open {}: {}", path.display(), e)
+            }
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        match e.kind() {
+            _ if is_timeout(&e) => Error::Timeout(e),
+            io::ErrorKind::ConnectionAborted => Error::Cancelled(e),
+            _ => Error::Io(e),
+        }
+    }
+}
+
+/// Returns `true` if `e` means that no data arrived in time. `Interrupted` is
+/// not a timeout: the operation that failed with it should be retried.
+pub fn is_timeout(e: &io::Error) -> bool {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => true,
+        _ => false,
+    }
+}
@@ -2,23 +2,56 @@
 
 extern crate serial;
 extern crate structopt;
+extern crate termios;
 #[macro_use]
 extern crate structopt_derive;
 extern crate xmodem;
 
-use std::io::{stdin, BufRead, BufReader};
-use std::path::PathBuf;
+use std::env;
+use std::fs::File;
+use std::io::{self, stdin, BufRead, BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::time::Duration;
 
 use serial::core::{BaudRate, CharSize, FlowControl, SerialDevice,
                    SerialPortSettings, StopBits};
 use structopt::StructOpt;
-use xmodem::{Progress, Xmodem};
+use xmodem::{Xmodem, XmodemConfig};
 
+mod devices;
+mod error;
+mod monitor;
 mod parsers;
+mod profile;
+mod raw;
+mod report;
+mod watch;
+mod zmodem;
 
-use parsers::{parse_baud_rate, parse_flow_control, parse_stop_bits,
-              parse_width};
+use error::Error;
+use monitor::{Action, Monitor};
+use profile::{Profile, Settings};
+use raw::{Newline, RawConfig};
+use report::Reporter;
+
+use parsers::{parse_baud_rate, parse_flow_control, parse_newline,
+              parse_protocol, parse_stop_bits, parse_width};
+
+/// The configuration file read for profiles unless `--config` is given,
+/// relative to the home directory.
+const CONFIG_FILE: &str = ".ttywrite";
+
+/// How long the monitor waits for device output before sending typed keys, in
+/// milliseconds.
+const MONITOR_POLL_MS: u64 = 10;
+
+/// The file transfer protocol used unless raw mode is enabled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Protocol {
+    Xmodem,
+    Zmodem,
+}
 
 #[derive(StructOpt, Debug)]
 #[structopt(about = "Write to TTY using the XMODEM protocol by default.")]
@@ -30,106 +63,351 @@ struct Opt {
 
     #[structopt(short = "b", long = "baud",
                 parse(try_from_str = "parse_baud_rate"),
-                help = "Set baud rate", default_value = "115200")]
-    baud_rate: BaudRate,
+                help = "Set baud rate [default: 115200]")]
+    baud_rate: Option<BaudRate>,
 
     #[structopt(short = "t", long = "timeout", parse(try_from_str),
-                help = "Set timeout in seconds", default_value = "10")]
-    timeout: u64,
+                help = "Set timeout in seconds [default: 10]")]
+    timeout: Option<u64>,
 
     #[structopt(short = "w", long = "width",
                 parse(try_from_str = "parse_width"),
-                help = "Set data character width in bits",
-                default_value = "8")]
-    char_width: CharSize,
+                help = "Set data character width in bits [default: 8]")]
+    char_width: Option<CharSize>,
 
-    #[structopt(help = "Path to TTY device", parse(from_os_str))]
-    tty_path: PathBuf,
+    #[structopt(help = "Path to TTY device (may be set by a profile)",
+                parse(from_os_str))]
+    tty_path: Option<PathBuf>,
 
     #[structopt(short = "f", long = "flow-control",
                 parse(try_from_str = "parse_flow_control"),
-                help = "Enable flow control ('hardware' or 'software')",
-                default_value = "none")]
-    flow_control: FlowControl,
+                help = "Enable flow control ('hardware' or 'software') \
+                        [default: none]")]
+    flow_control: Option<FlowControl>,
 
     #[structopt(short = "s", long = "stop-bits",
                 parse(try_from_str = "parse_stop_bits"),
-                help = "Set number of stop bits", default_value = "1")]
-    stop_bits: StopBits,
+                help = "Set number of stop bits [default: 1]")]
+    stop_bits: Option<StopBits>,
 
-    #[structopt(short = "r", long = "raw", help = "Disable XMODEM")] raw: bool,
+    #[structopt(short = "p", long = "protocol",
+                parse(try_from_str = "parse_protocol"),
+                help = "Set transfer protocol ('xmodem' or 'zmodem') \
+                        [default: xmodem]")]
+    protocol: Option<Protocol>,
+
+    #[structopt(short = "P", long = "profile",
+                help = "Use the settings of a profile in the config file")]
+    profile: Option<String>,
+
+    #[structopt(long = "config",
+                help = "Read profiles from a file [default: ~/.ttywrite]",
+                parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    #[structopt(long = "list",
+                help = "List serial devices that may be connected and exit")]
+    list: bool,
+
+    #[structopt(short = "r", long = "raw",
+                help = "Disable XMODEM/ZMODEM")]
+    raw: bool,
+
+    #[structopt(long = "newline", parse(try_from_str = "parse_newline"),
+                help = "Translate raw line endings ('lf', 'cr' or 'crlf')")]
+    newline: Option<Newline>,
+
+    #[structopt(long = "byte-delay", parse(try_from_str),
+                help = "Pause after every byte in raw mode, in milliseconds",
+                default_value = "0")]
+    byte_delay: u64,
+
+    #[structopt(long = "line-delay", parse(try_from_str),
+                help = "Pause after every line in raw mode, in milliseconds",
+                default_value = "0")]
+    line_delay: u64,
+
+    #[structopt(long = "verify-echo",
+                help = "Check that the device echoes every byte in raw mode")]
+    verify_echo: bool,
+
+    #[structopt(short = "m", long = "monitor",
+                help = "Keep the TTY open as a terminal after the transfer")]
+    monitor: bool,
+
+    #[structopt(short = "l", long = "log",
+                help = "Log monitored output to a file with timestamps",
+                parse(from_os_str))]
+    log: Option<PathBuf>,
+
+    #[structopt(long = "watch",
+                help = "Upload the input file with XMODEM whenever it changes")]
+    watch: bool,
+
+    #[structopt(long = "json",
+                help = "Print a JSON summary instead of progress")]
+    json: bool,
 }
 
 fn main() {
-    use std::fs::File;
-
     let opt = Opt::from_args();
+    let mut reporter = Reporter::new(opt.json);
+    if opt.list {
+        list_devices().unwrap_or_else(|e| fail(&reporter, e));
+        return;
+    }
+
+    if opt.watch && opt.input.is_none() {
+        let msg = "--watch requires an input file";
+        fail(&reporter, Error::Usage(msg.to_string()));
+    }
+
+    let settings = settings(&opt).unwrap_or_else(|e| fail(&reporter, e));
+    let mut serial = open(&settings).unwrap_or_else(|e| fail(&reporter, e));
+
+    let sent = transfer(&opt, &settings, &mut serial, &mut reporter);
+    reporter.finish(&sent);
+
+    let session = match (&opt.input, opt.watch, opt.monitor) {
+        (&Some(ref path), true, _) => watch(path, &settings, &mut serial),
+        (_, _, true) => monitor(&opt, &settings, &mut serial),
+        _ => Ok(()),
+    };
+
+    let session = match session {
+        Ok(()) => None,
+        Err(e) => {
+            let code = e.exit_code();
+            reporter.finish(&Err(e));
+            Some(code)
+        }
+    };
+
+    if let Some(code) = sent.err().map(|e| e.exit_code()).or(session) {
+        process::exit(code);
+    }
+}
+
+/// Reports `e` and exits with its exit code.
+fn fail(reporter: &Reporter, e: Error) -> ! {
+    let code = e.exit_code();
+    reporter.finish(&Err(e));
+    process::exit(code)
+}
+
+/// Prints the serial devices that may be connected to a Pi.
+fn list_devices() -> Result<(), Error> {
+    let devices = devices::list()?;
+    if devices.is_empty() {
+        println!("no serial devices found");
+    }
+
+    for device in devices {
+        println!("{}", device);
+    }
+
+    Ok(())
+}
+
+/// Returns the settings given on the command line, falling back to those in
+/// the selected profile and then to the defaults.
+fn settings(opt: &Opt) -> Result<Settings, Error> {
+    let command_line = Profile {
+        device: opt.tty_path.clone(),
+        baud_rate: opt.baud_rate,
+        timeout: opt.timeout,
+        char_width: opt.char_width,
+        flow_control: opt.flow_control,
+        stop_bits: opt.stop_bits,
+        protocol: opt.protocol,
+    };
+
+    let profile = match opt.profile {
+        Some(ref name) => load_profile(opt.config.as_ref(), name)?,
+        None => Profile::default(),
+    };
+
+    Ok(command_line.or(profile).settings())
+}
+
+/// Loads the profile called `name` from the configuration file at `path`, or
+/// `~/.ttywrite` if it is not set.
+fn load_profile(path: Option<&PathBuf>, name: &str) -> Result<Profile, Error> {
+    let path = match path {
+        Some(path) => path.clone(),
+        None => match env::home_dir() {
+            Some(home) => home.join(CONFIG_FILE),
+            None => {
+                let msg = "no home directory; use --config to locate profiles";
+                return Err(Error::Usage(msg.to_string()));
+            }
+        },
+    };
+
+    let mut config = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut config))
+        .map_err(|e| Error::File(path.clone(), e))?;
+
+    profile::find(&config, name)
+        .map_err(|msg| Error::Usage(format!("{}: {}", path.display(), msg)))
+}
+
+/// Opens the TTY in `settings` and applies the serial settings to it.
+fn open(settings: &Settings) -> Result<serial::SystemPort, Error> {
+    let path = match settings.device {
+        Some(ref path) => path,
+        None => {
+            let msg = "no TTY device given; pass its path or a --profile \
+                       that sets one";
+            return Err(Error::Usage(msg.to_string()));
+        }
+    };
+
     let mut serial =
-        serial::open(&opt.tty_path).expect("path points to invalid TTY");
-
-    let mut settings =
-        serial.read_settings().expect("Failed to load settings.");
-    settings
-        .set_baud_rate(opt.baud_rate)
-        .expect("Invalid baud rate.");
-    settings.set_char_size(opt.char_width);
-    settings.set_flow_control(opt.flow_control);
-    settings.set_stop_bits(opt.stop_bits);
-    serial
-        .write_settings(&settings)
-        .expect("Failed to apply serial settings");
+        serial::open(path).map_err(|e| Error::Open(path.clone(), e))?;
+
+    let mut port = serial.read_settings().map_err(Error::Settings)?;
+    port.set_baud_rate(settings.baud_rate)
+        .map_err(Error::Settings)?;
+    port.set_char_size(settings.char_width);
+    port.set_flow_control(settings.flow_control);
+    port.set_stop_bits(settings.stop_bits);
+    serial.write_settings(&port).map_err(Error::Settings)?;
 
     serial
-        .set_timeout(Duration::from_secs(opt.timeout))
-        .expect("Invalid timeout");
-
-    let sent_raw = match opt.input {
-        Some(file) => send_to_serial(
-            BufReader::new(File::open(file).expect("File open failed")),
-            &mut serial,
-            opt.raw,
+        .set_timeout(Duration::from_secs(settings.timeout))
+        .map_err(Error::Settings)?;
+    Ok(serial)
+}
+
+/// Uploads the input file at `path` whenever it changes, until interrupted.
+fn watch(
+    path: &Path,
+    settings: &Settings,
+    serial: &mut serial::SystemPort,
+) -> Result<(), Error> {
+    let timeout = Duration::from_secs(settings.timeout);
+    Ok(watch::watch(path, serial, settings.protocol, timeout)?)
+}
+
+/// Runs the interactive monitor, uploading the input again on request, until
+/// the user exits.
+fn monitor(
+    opt: &Opt,
+    settings: &Settings,
+    serial: &mut serial::SystemPort,
+) -> Result<(), Error> {
+    let log = match opt.log {
+        Some(ref path) => Some(
+            File::create(path).map_err(|e| Error::File(path.clone(), e))?,
         ),
-        None => send_to_serial(BufReader::new(stdin()), &mut serial, opt.raw),
+        None => None,
     };
 
-    match sent_raw {
-        Ok(n) => println!("wrote {} bytes to input", n),
-        Err(e) => println!("error: {:?}", e),
+    let mut monitor = Monitor::new(log);
+    loop {
+        serial
+            .set_timeout(Duration::from_millis(MONITOR_POLL_MS))
+            .map_err(Error::Settings)?;
+        match monitor.run(serial, opt.input.is_some())? {
+            Action::Upload => {
+                serial
+                    .set_timeout(Duration::from_secs(settings.timeout))
+                    .map_err(Error::Settings)?;
+                println!("");
+
+                let mut reporter = Reporter::new(false);
+                let sent = transfer(opt, settings, serial, &mut reporter);
+                reporter.finish(&sent);
+            }
+            Action::Exit => return Ok(()),
+        }
     }
 }
 
+/// Sends the input selected by `opt` to `serial` using the protocol in
+/// `settings`, reporting progress to `reporter`.
+fn transfer(
+    opt: &Opt,
+    settings: &Settings,
+    serial: &mut serial::SerialPort,
+    reporter: &mut Reporter,
+) -> Result<usize, Error> {
+    let sent = match (opt.input.as_ref(), settings.protocol, opt.raw) {
+        (Some(path), Protocol::Zmodem, false) => {
+            let file = open_input(path)?;
+            send_zmodem(&file_name(path), file, serial, reporter)
+        }
+        (None, Protocol::Zmodem, false) => {
+            let mut data = vec![];
+            stdin().read_to_end(&mut data)?;
+            send_zmodem("stdin", Cursor::new(data), serial, reporter)
+        }
+        (Some(path), _, _) => {
+            let file = open_input(path)?;
+            let len = file.metadata().ok().map(|m| m.len() as usize);
+            let input = BufReader::new(file);
+            send_to_serial(input, serial, opt, len, reporter)
+        }
+        (None, _, _) => {
+            let input = BufReader::new(stdin());
+            send_to_serial(input, serial, opt, None, reporter)
+        }
+    };
+
+    Ok(sent?)
+}
+
+/// Opens the input file at `path`.
+fn open_input(path: &Path) -> Result<File, Error> {
+    File::open(path).map_err(|e| Error::File(path.to_path_buf(), e))
+}
+
 fn send_to_serial<I: BufRead>(
-    mut input: I,
+    input: I,
     serial: &mut serial::SerialPort,
-    raw: bool,
-) -> Result<usize, std::io::Error> {
-    match raw {
-        true => serial.write(input.fill_buf()?),
-        false => Xmodem::transmit_with_progress(input, serial, progress_fn),
+    opt: &Opt,
+    len: Option<usize>,
+    reporter: &mut Reporter,
+) -> Result<usize, io::Error> {
+    let config = XmodemConfig {
+        length_hint: len,
+        ..XmodemConfig::default()
+    };
+
+    match opt.raw {
+        true => raw::send(input, serial, raw_config(opt)),
+        false => Xmodem::transmit_with_config(input, serial, config, |p| {
+            reporter.progress(p)
+        }),
     }
 }
 
-fn progress_fn(progress: Progress) {
-    use std::io::Write;
-    static mut LAST_PKT: u8 = 0;
+/// Returns the raw mode configuration selected by `opt`.
+fn raw_config(opt: &Opt) -> RawConfig {
+    RawConfig {
+        byte_delay: Duration::from_millis(opt.byte_delay),
+        line_delay: Duration::from_millis(opt.line_delay),
+        newline: opt.newline,
+        verify: opt.verify_echo,
+    }
+}
 
-    match progress {
-        Progress::Started => {
-            println!("Transmission started:");
-        }
-        Progress::Waiting => println!("Waiting for initial NAK"),
-        Progress::Packet(p) => {
-            if unsafe { p == LAST_PKT } {
-                print!("@");
-            } else {
-                unsafe { LAST_PKT = p };
-                print!("#")
-            }
-            std::io::stdout().flush().unwrap();
-        }
-        Progress::Terminated(p) => {
-            println!("");
-            println!("| wrote {} packets", p);
-        }
+/// Sends `input` as a file called `name` using ZMODEM.
+fn send_zmodem<I: Read + Seek>(
+    name: &str,
+    input: I,
+    serial: &mut serial::SerialPort,
+    reporter: &mut Reporter,
+) -> Result<usize, io::Error> {
+    zmodem::send(name, input, serial, |p| reporter.progress(p))
+}
+
+/// Returns the file name of `path`, as announced to a ZMODEM receiver.
+fn file_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_string_lossy().into_owned(),
     }
 }
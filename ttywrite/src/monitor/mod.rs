@@ -0,0 +1,281 @@
+//! An interactive terminal on the serial port.
+//!
+//! While monitoring, everything the device sends is written to stdout (and
+//! optionally a log) and everything typed on stdin is sent to the device.
+//! Keys typed after the escape key, `Ctrl-A`, control the monitor instead.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use termios::{self, Termios};
+
+use error::is_timeout;
+
+#[cfg(test)]
+mod tests;
+
+/// The escape key: `Ctrl-A`.
+pub const ESCAPE: u8 = 0x01;
+
+/// The help text printed for `Ctrl-A ?`.
+pub const HELP: &str = "Ctrl-A u: upload the input file again\r\n\
+                        Ctrl-A q: exit\r\n\
+                        Ctrl-A Ctrl-A: send Ctrl-A\r\n";
+
+/// What the user asked the monitor to do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Upload the input file again and resume monitoring.
+    Upload,
+    /// End the session.
+    Exit,
+}
+
+/// The meaning of a key typed by the user.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Key {
+    /// Send the byte to the device.
+    Send(u8),
+    /// Perform the action.
+    Action(Action),
+    /// Print the help text.
+    Help,
+    /// Do nothing: the escape key, or an unknown key following it.
+    Nothing,
+}
+
+/// Interprets typed keys, recognizing commands following `ESCAPE`.
+#[derive(Debug, Default)]
+struct Keys {
+    escaped: bool,
+}
+
+impl Keys {
+    fn feed(&mut self, byte: u8) -> Key {
+        if !self.escaped {
+            self.escaped = byte == ESCAPE;
+            return match self.escaped {
+                true => Key::Nothing,
+                false => Key::Send(byte),
+            };
+        }
+
+        self.escaped = false;
+        match byte {
+            ESCAPE => Key::Send(ESCAPE),
+            b'u' | b'U' => Key::Action(Action::Upload),
+            b'q' | b'Q' | b'x' | b'X' => Key::Action(Action::Exit),
+            b'?' | b'h' | b'H' => Key::Help,
+            _ => Key::Nothing,
+        }
+    }
+}
+
+/// Writes device output to a log, prefixing each line with the time at which
+/// it started, relative to the start of the session.
+pub struct Log<W> {
+    out:        W,
+    line_start: bool,
+}
+
+impl<W: Write> Log<W> {
+    pub fn new(out: W) -> Log<W> {
+        Log {
+            out,
+            line_start: true,
+        }
+    }
+
+    /// Records `bytes` received `at` the given time since the start of the
+    /// session.
+    pub fn record(&mut self, bytes: &[u8], at: Duration) -> io::Result<()> {
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            if self.line_start {
+                let millis = at.subsec_nanos() / 1_000_000;
+                write!(self.out, "[{:5}.{:03}] ", at.as_secs(), millis)?;
+            }
+
+            let end = match rest.iter().position(|b| *b == b'\n') {
+                Some(i) => i + 1,
+                None => rest.len(),
+            };
+
+            self.out.write_all(&rest[..end])?;
+            self.line_start = rest[end - 1] == b'\n';
+            rest = &rest[end..];
+        }
+
+        self.out.flush()
+    }
+}
+
+/// Puts the terminal on stdin in raw input mode until dropped: keys are
+/// passed on as they are typed, without echo, line editing or signals.
+/// Output processing is left enabled.
+struct RawMode {
+    fd:       RawFd,
+    original: Termios,
+}
+
+impl RawMode {
+    /// Returns `None` if stdin is not a terminal.
+    fn enable() -> Option<RawMode> {
+        let fd = io::stdin().as_raw_fd();
+        let original = Termios::from_fd(fd).ok()?;
+
+        let mut raw = original;
+        raw.c_iflag &= !(termios::ICRNL | termios::IXON | termios::ISTRIP);
+        raw.c_lflag &= !(termios::ECHO
+            | termios::ICANON
+            | termios::IEXTEN
+            | termios::ISIG);
+        raw.c_cc[termios::VMIN] = 1;
+        raw.c_cc[termios::VTIME] = 0;
+        termios::tcsetattr(fd, termios::TCSANOW, &raw).ok()?;
+
+        Some(RawMode { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let original = &self.original;
+        let _ = termios::tcsetattr(self.fd, termios::TCSANOW, original);
+    }
+}
+
+/// Returns a channel yielding every byte read from stdin.
+fn stdin_keys() -> Receiver<u8> {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for byte in stdin.lock().bytes() {
+            match byte {
+                Ok(byte) if tx.send(byte).is_ok() => {}
+                _ => break,
+            }
+        }
+    });
+
+    rx
+}
+
+/// An interactive session bridging stdin and stdout to a device.
+pub struct Monitor {
+    keys:  Receiver<u8>,
+    state: Keys,
+    log:   Option<Log<File>>,
+    start: Instant,
+    _raw:  Option<RawMode>,
+}
+
+impl Monitor {
+    /// Starts a session, putting the terminal in raw input mode and logging
+    /// device output to `log` if it is set. Stdin is read from now on.
+    pub fn new(log: Option<File>) -> Monitor {
+        Monitor {
+            _raw: RawMode::enable(),
+            ..Monitor::with_keys(stdin_keys(), log)
+        }
+    }
+
+    /// Returns a session reading typed keys from `keys`.
+    fn with_keys(keys: Receiver<u8>, log: Option<File>) -> Monitor {
+        Monitor {
+            keys,
+            state: Keys::default(),
+            log: log.map(Log::new),
+            start: Instant::now(),
+            _raw: None,
+        }
+    }
+
+    /// Bridges `port` and the terminal until the user asks for an action.
+    /// Reads from `port` should time out quickly, as typed keys are only sent
+    /// in between reads. If `can_upload` is not set, requests to upload are
+    /// refused.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading or writing to `port`, stdout or the log
+    /// fails. Read timeouts are not errors.
+    pub fn run<T: Read + Write>(
+        &mut self,
+        port: &mut T,
+        can_upload: bool,
+    ) -> io::Result<Action> {
+        let mut buf = [0u8; 1024];
+        loop {
+            match port.read(&mut buf) {
+                Ok(n) => self.output(&buf[..n])?,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if is_timeout(e) => {}
+                Err(e) => return Err(e),
+            }
+
+            let mut typed = vec![];
+            loop {
+                let byte = match self.keys.try_recv() {
+                    Ok(byte) => byte,
+                    Err(TryRecvError::Empty) => break,
+                    // Stdin was closed; keep showing device output.
+                    Err(TryRecvError::Disconnected) => break,
+                };
+
+                match self.state.feed(byte) {
+                    Key::Send(byte) => typed.push(byte),
+                    Key::Action(Action::Upload) if !can_upload => {
+                        self.notice("no input file to upload")?
+                    }
+                    Key::Action(action) => {
+                        port.write_all(&typed)?;
+                        return Ok(action);
+                    }
+                    Key::Help => {
+                        print!("\r\n{}", HELP);
+                        io::stdout().flush()?;
+                    }
+                    Key::Nothing => {}
+                }
+            }
+
+            port.write_all(&typed)?;
+            port.flush()?;
+        }
+    }
+
+    /// Prints a message from the monitor itself. It is not logged, as the
+    /// log only records what the device sent.
+    pub fn notice(&mut self, msg: &str) -> io::Result<()> {
+        let line = format!("\r\n--- {} ---\r\n", msg);
+        show(line.as_bytes())
+    }
+
+    /// Writes `bytes` received from the device to stdout and the log.
+    fn output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        show(bytes)?;
+
+        let at = self.start.elapsed();
+        match self.log {
+            Some(ref mut log) => log.record(bytes, at),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes `bytes` to stdout.
+fn show(bytes: &[u8]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write_all(bytes)?;
+    stdout.flush()
+}
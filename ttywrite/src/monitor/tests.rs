@@ -0,0 +1,99 @@
+use super::*;
+use std::env;
+use std::io::Cursor;
+use std::sync::mpsc::channel;
+
+/// A device that never sends anything, recording what is written to it.
+struct Silent(Vec<u8>);
+
+impl io::Read for Silent {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+    }
+}
+
+impl io::Write for Silent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+fn monitor(typed: &[u8]) -> Monitor {
+    let (tx, rx) = channel();
+    typed.iter().for_each(|b| tx.send(*b).unwrap());
+    Monitor::with_keys(rx, None)
+}
+
+#[test]
+fn test_keys() {
+    let mut keys = Keys::default();
+    assert_eq!(keys.feed(b'a'), Key::Send(b'a'));
+    assert_eq!(keys.feed(ESCAPE), Key::Nothing);
+    assert_eq!(keys.feed(ESCAPE), Key::Send(ESCAPE));
+    assert_eq!(keys.feed(ESCAPE), Key::Nothing);
+    assert_eq!(keys.feed(b'u'), Key::Action(Action::Upload));
+    assert_eq!(keys.feed(b'q'), Key::Send(b'q'));
+    assert_eq!(keys.feed(ESCAPE), Key::Nothing);
+    assert_eq!(keys.feed(b'q'), Key::Action(Action::Exit));
+    assert_eq!(keys.feed(ESCAPE), Key::Nothing);
+    assert_eq!(keys.feed(b'z'), Key::Nothing);
+    assert_eq!(keys.feed(b'z'), Key::Send(b'z'));
+}
+
+#[test]
+fn test_log_timestamps_lines() {
+    let mut log = Log::new(Cursor::new(vec![]));
+    log.record(b"boot\r\nok", Duration::from_millis(1_500))
+        .expect("record");
+    log.record(b"\n", Duration::from_millis(1_750)).expect("record");
+    log.record(b"more\n", Duration::from_millis(62_005))
+        .expect("record");
+
+    let text = String::from_utf8(log.out.into_inner()).unwrap();
+    assert_eq!(
+        text,
+        "[    1.500] boot\r\n[    1.500] ok\n[   62.005] more\n"
+    );
+}
+
+#[test]
+fn test_run_sends_typed_keys() {
+    let mut port = Silent(vec![]);
+    let mut monitor = monitor(b"ls\r\x01\x01\x01q");
+    assert_eq!(monitor.run(&mut port, true).expect("run"), Action::Exit);
+    assert_eq!(&port.0, b"ls\r\x01");
+}
+
+#[test]
+fn test_run_upload() {
+    let mut port = Silent(vec![]);
+    let mut monitor = monitor(b"\x01u\x01x");
+    assert_eq!(monitor.run(&mut port, true).expect("run"), Action::Upload);
+    assert_eq!(monitor.run(&mut port, true).expect("run"), Action::Exit);
+    assert!(port.0.is_empty());
+}
+
+#[test]
+fn test_notices_are_not_logged() {
+    let path = env::temp_dir().join("ttywrite-monitor-notice");
+    let log = File::create(&path).expect("create log");
+
+    let (tx, rx) = channel();
+    b"\x01u\x01q".iter().for_each(|b| tx.send(*b).unwrap());
+    let mut monitor = Monitor::with_keys(rx, Some(log));
+
+    let mut port = Silent(vec![]);
+    assert_eq!(monitor.run(&mut port, false).expect("run"), Action::Exit);
+    monitor.notice("uploaded").expect("notice");
+    monitor.output(b"ok\n").expect("output");
+
+    let mut text = String::new();
+    File::open(&path)
+        .and_then(|mut log| log.read_to_string(&mut text))
+        .expect("read log");
+    assert!(text.ends_with("] ok\n"), "{:?}", text);
+    assert!(!text.contains("---"), "{:?}", text);
+}
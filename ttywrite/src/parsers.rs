@@ -1,5 +1,8 @@
 use serial::core::{BaudRate, CharSize, FlowControl, StopBits};
 
+use Protocol;
+use raw::Newline;
+
 pub fn parse_width(s: &str) -> Result<CharSize, &str> {
     match s {
         "5" => Ok(CharSize::Bits5),
@@ -33,3 +36,20 @@ pub fn parse_flow_control(s: &str) -> Result<FlowControl, &str> {
 pub fn parse_baud_rate(s: &str) -> Result<BaudRate, ::std::num::ParseIntError> {
     Ok(BaudRate::from_speed(s.parse()?))
 }
+
+pub fn parse_protocol(s: &str) -> Result<Protocol, &str> {
+    match s {
+        "xmodem" => Ok(Protocol::Xmodem),
+        "zmodem" => Ok(Protocol::Zmodem),
+        _ => Err("value must be 'xmodem' or 'zmodem'"),
+    }
+}
+
+pub fn parse_newline(s: &str) -> Result<Newline, &str> {
+    match s {
+        "lf" => Ok(Newline::Lf),
+        "cr" => Ok(Newline::Cr),
+        "crlf" => Ok(Newline::CrLf),
+        _ => Err("value must be 'lf', 'cr' or 'crlf'"),
+    }
+}
@@ -0,0 +1,171 @@
+//! Named serial settings loaded from a configuration file.
+//!
+//! The file holds one section per profile, named in brackets, listing
+//! settings by the name of their command line option:
+//!
+//! ```text
+//! # ~/.ttywrite
+//! [pi3]
+//! device = /dev/ttyUSB0
+//! baud = 115200
+//! flow-control = none
+//! stop-bits = 1
+//! protocol = xmodem
+//! ```
+//!
+//! `width` and `timeout` may be set as well. Blank lines and lines starting
+//! with `#` are ignored.
+
+use std::path::PathBuf;
+
+use serial::core::{BaudRate, CharSize, FlowControl, StopBits};
+
+use parsers::{parse_baud_rate, parse_flow_control, parse_protocol,
+              parse_stop_bits, parse_width};
+use Protocol;
+
+#[cfg(test)]
+mod tests;
+
+/// Serial settings, any of which may be left unset.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub device:       Option<PathBuf>,
+    pub baud_rate:    Option<BaudRate>,
+    pub timeout:      Option<u64>,
+    pub char_width:   Option<CharSize>,
+    pub flow_control: Option<FlowControl>,
+    pub stop_bits:    Option<StopBits>,
+    pub protocol:     Option<Protocol>,
+}
+
+/// Complete serial settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub device:       Option<PathBuf>,
+    pub baud_rate:    BaudRate,
+    pub timeout:      u64,
+    pub char_width:   CharSize,
+    pub flow_control: FlowControl,
+    pub stop_bits:    StopBits,
+    pub protocol:     Protocol,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            device: None,
+            baud_rate: BaudRate::Baud115200,
+            timeout: 10,
+            char_width: CharSize::Bits8,
+            flow_control: FlowControl::FlowNone,
+            stop_bits: StopBits::Stop1,
+            protocol: Protocol::Xmodem,
+        }
+    }
+}
+
+impl Profile {
+    /// Returns a profile with the settings of `self`, falling back to those
+    /// of `other` for settings left unset.
+    pub fn or(self, other: Profile) -> Profile {
+        Profile {
+            device: self.device.or(other.device),
+            baud_rate: self.baud_rate.or(other.baud_rate),
+            timeout: self.timeout.or(other.timeout),
+            char_width: self.char_width.or(other.char_width),
+            flow_control: self.flow_control.or(other.flow_control),
+            stop_bits: self.stop_bits.or(other.stop_bits),
+            protocol: self.protocol.or(other.protocol),
+        }
+    }
+
+    /// Returns the settings of `self`, using the defaults for those left
+    /// unset.
+    pub fn settings(self) -> Settings {
+        let default = Settings::default();
+        Settings {
+            device: self.device,
+            baud_rate: self.baud_rate.unwrap_or(default.baud_rate),
+            timeout: self.timeout.unwrap_or(default.timeout),
+            char_width: self.char_width.unwrap_or(default.char_width),
+            flow_control: self.flow_control.unwrap_or(default.flow_control),
+            stop_bits: self.stop_bits.unwrap_or(default.stop_bits),
+            protocol: self.protocol.unwrap_or(default.protocol),
+        }
+    }
+
+    /// Sets the setting named `key` to `value`.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "device" => self.device = Some(PathBuf::from(value)),
+            "baud" => {
+                let baud = parse_baud_rate(value).map_err(|e| e.to_string())?;
+                self.baud_rate = Some(baud);
+            }
+            "timeout" => {
+                let timeout = value.parse().map_err(|e| format!("{}", e))?;
+                self.timeout = Some(timeout);
+            }
+            "width" => self.char_width = Some(parse_width(value)?),
+            "flow-control" => {
+                self.flow_control = Some(parse_flow_control(value)?)
+            }
+            "stop-bits" => self.stop_bits = Some(parse_stop_bits(value)?),
+            "protocol" => self.protocol = Some(parse_protocol(value)?),
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the profiles in `config`, in the order they are defined.
+///
+/// # Errors
+///
+/// Returns a message naming the offending line if `config` is malformed or
+/// sets an unknown or invalid setting.
+pub fn parse(config: &str) -> Result<Vec<(String, Profile)>, String> {
+    let mut profiles: Vec<(String, Profile)> = vec![];
+    for (i, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = |msg: String| format!("line {}: {}", i + 1, msg);
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_string();
+            profiles.push((name, Profile::default()));
+            continue;
+        }
+
+        let (key, value) = match line.find('=') {
+            Some(eq) => (line[..eq].trim(), line[eq + 1..].trim()),
+            None => return Err(error("expected 'key = value'".to_string())),
+        };
+
+        match profiles.last_mut() {
+            Some(&mut (_, ref mut profile)) => {
+                profile.set(key, value).map_err(error)?
+            }
+            None => return Err(error(format!("'{}' outside a profile", key))),
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// Returns the profile called `name` in `config`.
+///
+/// # Errors
+///
+/// Returns a message if `config` is malformed or has no such profile.
+pub fn find(config: &str, name: &str) -> Result<Profile, String> {
+    parse(config)?
+        .into_iter()
+        .find(|&(ref profile, _)| profile == name)
+        .map(|(_, profile)| profile)
+        .ok_or_else(|| format!("no profile named '{}'", name))
+}
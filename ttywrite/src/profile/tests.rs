@@ -0,0 +1,76 @@
+use super::*;
+
+const CONFIG: &str = "
+# Team adapters.
+[pi3]
+device = /dev/ttyUSB0
+baud = 57600
+flow-control = hardware
+stop-bits = 2
+protocol = zmodem
+
+[mac]
+device=/dev/tty.SLAB_USBtoUART
+timeout = 30
+width = 7
+";
+
+#[test]
+fn test_parse() {
+    let profiles = parse(CONFIG).expect("parsed");
+    assert_eq!(profiles.len(), 2);
+
+    let (ref name, ref pi3) = profiles[0];
+    assert_eq!(name, "pi3");
+    assert_eq!(
+        *pi3,
+        Profile {
+            device: Some(PathBuf::from("/dev/ttyUSB0")),
+            baud_rate: Some(BaudRate::Baud57600),
+            flow_control: Some(FlowControl::FlowHardware),
+            stop_bits: Some(StopBits::Stop2),
+            protocol: Some(Protocol::Zmodem),
+            ..Profile::default()
+        }
+    );
+
+    let mac = find(CONFIG, "mac").expect("found");
+    assert_eq!(mac.device, Some(PathBuf::from("/dev/tty.SLAB_USBtoUART")));
+    assert_eq!(mac.timeout, Some(30));
+    assert_eq!(mac.char_width, Some(CharSize::Bits7));
+    assert_eq!(mac.baud_rate, None);
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |config: &str| parse(config).expect_err("invalid");
+    assert_eq!(error("baud = 9600"), "line 1: 'baud' outside a profile");
+    assert_eq!(error("[a]\n\nbaud"), "line 3: expected 'key = value'");
+    assert_eq!(error("[a]\nparity = odd"), "line 2: unknown setting 'parity'");
+    assert_eq!(
+        error("[a]\nstop-bits = 3"),
+        "line 2: value must '1' or '2'"
+    );
+    assert!(error("[a]\nbaud = fast").starts_with("line 2: "));
+
+    let missing = find(CONFIG, "pi4").expect_err("missing");
+    assert_eq!(missing, "no profile named 'pi4'");
+}
+
+#[test]
+fn test_precedence() {
+    let command_line = Profile {
+        baud_rate: Some(BaudRate::Baud9600),
+        ..Profile::default()
+    };
+
+    let pi3 = find(CONFIG, "pi3").expect("found");
+    let settings = command_line.or(pi3).settings();
+    assert_eq!(settings.device, Some(PathBuf::from("/dev/ttyUSB0")));
+    assert_eq!(settings.baud_rate, BaudRate::Baud9600);
+    assert_eq!(settings.stop_bits, StopBits::Stop2);
+    assert_eq!(settings.timeout, 10);
+    assert_eq!(settings.char_width, CharSize::Bits8);
+
+    assert_eq!(Profile::default().settings(), Settings::default());
+}
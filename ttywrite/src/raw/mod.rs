@@ -0,0 +1,210 @@
+//! Sending input as-is, without a transfer protocol.
+//!
+//! The whole input is streamed to the device, optionally translating line
+//! endings, pausing after every byte or line for devices that cannot keep up,
+//! and checking that the device echoes back everything it is sent.
+
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use error::is_timeout;
+
+#[cfg(test)]
+mod tests;
+
+/// How many bytes are written to the device at once when not pacing.
+const CHUNK_SIZE: usize = 4096;
+
+/// The line ending sent in place of each line ending in the input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Newline {
+    Lf,
+    Cr,
+    CrLf,
+}
+
+impl Newline {
+    fn bytes(&self) -> &'static [u8] {
+        match *self {
+            Newline::Lf => b"\n",
+            Newline::Cr => b"\r",
+            Newline::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// Configuration for raw transfers.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RawConfig {
+    /// The pause after every byte sent.
+    pub byte_delay: Duration,
+    /// The pause after every line sent.
+    pub line_delay: Duration,
+    /// If set, both `\n` and `\r\n` in the input are sent as this line
+    /// ending. Otherwise the input is sent unchanged.
+    pub newline:    Option<Newline>,
+    /// Whether every byte sent must be echoed back by the device before the
+    /// next one is sent.
+    pub verify:     bool,
+}
+
+/// Writes bytes to a device, pacing them and verifying their echo as
+/// configured.
+struct Pacer<'a, T: 'a + ?Sized> {
+    port:   &'a mut T,
+    config: RawConfig,
+    buffer: Vec<u8>,
+    sent:   usize,
+}
+
+impl<'a, T: Read + Write + ?Sized> Pacer<'a, T> {
+    fn new(port: &'a mut T, config: RawConfig) -> Pacer<'a, T> {
+        Pacer {
+            port,
+            config,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            sent: 0,
+        }
+    }
+
+    /// Sends `bytes`, pausing afterwards if they end a line.
+    fn send(&mut self, bytes: &[u8], line_end: bool) -> io::Result<()> {
+        let byte_delay = self.config.byte_delay;
+        for &byte in bytes {
+            if !self.config.verify && byte_delay == Duration::default() {
+                self.buffer.push(byte);
+                continue;
+            }
+
+            self.port.write_all(&[byte])?;
+            self.port.flush()?;
+            if self.config.verify {
+                self.verify(byte)?;
+            }
+
+            self.sent += 1;
+            pause(byte_delay);
+        }
+
+        let line_delay = self.config.line_delay;
+        if line_end && line_delay != Duration::default() {
+            self.flush()?;
+            pause(line_delay);
+        } else if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the device's echo of `byte`, the `self.sent`th byte sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the echo differs from `byte`
+    /// and `TimedOut` if the device does not echo it in time.
+    fn verify(&mut self, byte: u8) -> io::Result<()> {
+        let mut echo = [0u8; 1];
+        loop {
+            match self.port.read(&mut echo) {
+                Ok(1) if echo[0] == byte => return Ok(()),
+                Ok(1) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "echo mismatch at byte {}: sent {:#04x}, \
+                             received {:#04x}",
+                            self.sent, byte, echo[0]
+                        ),
+                    ))
+                }
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("device closed before echoing byte {}",
+                                self.sent),
+                    ))
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if is_timeout(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("no echo for byte {}", self.sent),
+                    ))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Writes any buffered bytes to the device.
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.write_all(&self.buffer)?;
+        self.port.flush()?;
+        self.sent += self.buffer.len();
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+fn pause(delay: Duration) {
+    if delay != Duration::default() {
+        thread::sleep(delay);
+    }
+}
+
+/// Sends all of `input` to `port` as configured by `config`, returning the
+/// number of bytes sent after line ending translation.
+///
+/// # Errors
+///
+/// Returns an error if reading `input` or writing to `port` fails, or, when
+/// verifying echoes, as soon as the device echoes a byte other than the one
+/// sent or does not echo it before `port`'s read timeout.
+pub fn send<R: Read, T: Read + Write + ?Sized>(
+    mut input: R,
+    port: &mut T,
+    config: RawConfig,
+) -> io::Result<usize> {
+    let mut pacer = Pacer::new(port, config);
+    let mut buf = [0u8; CHUNK_SIZE];
+    let mut held_cr = false;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for &byte in &buf[..n] {
+            let newline = match config.newline {
+                Some(newline) => newline,
+                None => {
+                    pacer.send(&[byte], byte == b'\n')?;
+                    continue;
+                }
+            };
+
+            // A `\r` is only part of a line ending if a `\n` follows it.
+            if held_cr && byte != b'\n' {
+                pacer.send(b"\r", false)?;
+            }
+
+            held_cr = byte == b'\r';
+            match byte {
+                b'\r' => {}
+                b'\n' => pacer.send(newline.bytes(), true)?,
+                _ => pacer.send(&[byte], false)?,
+            }
+        }
+    }
+
+    if held_cr {
+        pacer.send(b"\r", false)?;
+    }
+
+    pacer.flush()?;
+    Ok(pacer.sent)
+}
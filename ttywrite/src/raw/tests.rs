@@ -0,0 +1,150 @@
+use super::*;
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::time::Instant;
+
+/// A device recording what is written to it and echoing it back, unless it
+/// is `silent`. The byte written at index `corrupt` is echoed damaged.
+#[derive(Default)]
+struct Echo {
+    written: Vec<u8>,
+    echo:    VecDeque<u8>,
+    writes:  usize,
+    corrupt: Option<usize>,
+    silent:  bool,
+}
+
+impl io::Read for Echo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.echo.pop_front() {
+            Some(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            None => Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+        }
+    }
+}
+
+impl io::Write for Echo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            let echo = match self.corrupt == Some(self.written.len()) {
+                true => byte ^ 0x20,
+                false => byte,
+            };
+
+            self.written.push(byte);
+            if !self.silent {
+                self.echo.push_back(echo);
+            }
+        }
+
+        self.writes += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+fn send_to(
+    port: &mut Echo,
+    input: &[u8],
+    config: RawConfig,
+) -> io::Result<usize> {
+    send(Cursor::new(input.to_vec()), port, config)
+}
+
+#[test]
+fn test_streams_whole_input() {
+    let input: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+    let mut port = Echo::default();
+    let sent = send_to(&mut port, &input, RawConfig::default());
+
+    assert_eq!(sent.expect("sent"), input.len());
+    assert_eq!(port.written, input);
+    assert_eq!(port.writes, 5);
+}
+
+#[test]
+fn test_newline_translation() {
+    let input = b"a\nb\r\nc\rd\r\r\n\r";
+    let expected: &[(Option<Newline>, &[u8])] = &[
+        (None, b"a\nb\r\nc\rd\r\r\n\r"),
+        (Some(Newline::Lf), b"a\nb\nc\rd\r\n\r"),
+        (Some(Newline::Cr), b"a\rb\rc\rd\r\r\r"),
+        (Some(Newline::CrLf), b"a\r\nb\r\nc\rd\r\r\n\r"),
+    ];
+
+    for &(newline, output) in expected {
+        let mut port = Echo::default();
+        let config = RawConfig {
+            newline,
+            ..RawConfig::default()
+        };
+
+        let sent = send_to(&mut port, input, config).expect("sent");
+        assert_eq!(sent, output.len());
+        assert_eq!(&port.written[..], output, "{:?}", newline);
+    }
+}
+
+#[test]
+fn test_pacing() {
+    let mut port = Echo::default();
+    let config = RawConfig {
+        byte_delay: Duration::from_millis(2),
+        line_delay: Duration::from_millis(10),
+        ..RawConfig::default()
+    };
+
+    let start = Instant::now();
+    send_to(&mut port, b"ab\ncd\n", config).expect("sent");
+    assert!(start.elapsed() >= Duration::from_millis(32));
+    assert_eq!(port.writes, 6);
+
+    let mut port = Echo::default();
+    let config = RawConfig {
+        line_delay: Duration::from_millis(10),
+        ..RawConfig::default()
+    };
+
+    let start = Instant::now();
+    send_to(&mut port, b"one\ntwo\nthree", config).expect("sent");
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(port.writes, 3);
+}
+
+#[test]
+fn test_verify_echo() {
+    let config = RawConfig {
+        verify: true,
+        ..RawConfig::default()
+    };
+
+    let mut port = Echo::default();
+    assert_eq!(send_to(&mut port, b"hello\n", config).expect("sent"), 6);
+    assert!(port.echo.is_empty());
+
+    let mut port = Echo {
+        corrupt: Some(3),
+        ..Echo::default()
+    };
+
+    let e = send_to(&mut port, b"hello\n", config).expect_err("mismatch");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        e.to_string(),
+        "echo mismatch at byte 3: sent 0x6c, received 0x4c"
+    );
+    assert_eq!(port.written, b"hell");
+
+    let mut port = Echo {
+        silent: true,
+        ..Echo::default()
+    };
+
+    let e = send_to(&mut port, b"hello\n", config).expect_err("no echo");
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(port.written, b"h");
+}
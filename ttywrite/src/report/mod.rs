@@ -0,0 +1,155 @@
+//! Reporting the progress and outcome of transfers.
+//!
+//! By default progress is drawn on stdout as the transfer goes and errors are
+//! printed to stderr. In JSON mode, nothing is printed until the transfer
+//! ends, when a single line summarizing it is written to stdout.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+use xmodem::{Progress, Stats};
+
+use error::Error;
+
+#[cfg(test)]
+mod tests;
+
+/// Records the progress of a transfer and reports it to the user.
+pub struct Reporter {
+    json:         bool,
+    last_percent: Option<usize>,
+    stats:        Stats,
+}
+
+impl Reporter {
+    /// Returns a reporter printing progress as text, or, if `json` is set,
+    /// only a JSON summary once the transfer ends.
+    pub fn new(json: bool) -> Reporter {
+        Reporter {
+            json,
+            last_percent: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Records `progress`, printing it unless reporting JSON.
+    pub fn progress(&mut self, progress: Progress) {
+        match progress {
+            Progress::Packet { stats, .. }
+            | Progress::Retry { stats, .. }
+            | Progress::Cancelled { stats, .. }
+            | Progress::Terminated { stats } => self.stats = stats,
+            Progress::Waiting | Progress::Started => {}
+        }
+
+        if !self.json {
+            print_progress(progress, &mut self.last_percent);
+        }
+    }
+
+    /// Reports the outcome of the transfer: the number of bytes sent or the
+    /// error that ended it. Errors ending the watch or monitor session that
+    /// follows the transfer are reported the same way.
+    pub fn finish(&self, result: &Result<usize, Error>) {
+        match (self.json, result) {
+            (true, _) => println!("{}", self.summary(result)),
+            (false, &Ok(n)) => println!("wrote {} bytes to input", n),
+            (false, &Err(ref e)) => eprintln!("error: {}", e),
+        }
+    }
+
+    /// Returns the JSON summary of a transfer ending with `result`.
+    fn summary(&self, result: &Result<usize, Error>) -> String {
+        let mut json = String::from("{");
+        let bytes = match *result {
+            Ok(n) => {
+                json.push_str("\"status\":\"ok\",\"exit_code\":0");
+                n
+            }
+            Err(ref e) => {
+                let _ = write!(
+                    json,
+                    "\"status\":\"error\",\"exit_code\":{},\"error\":{},\
+                     \"message\":{}",
+                    e.exit_code(),
+                    json_string(e.name()),
+                    json_string(&e.to_string())
+                );
+                self.stats.bytes
+            }
+        };
+
+        let _ = write!(
+            json,
+            ",\"bytes\":{},\"packets\":{},\"retries\":{},\"elapsed_ms\":{}}}",
+            bytes,
+            self.stats.packets,
+            self.stats.retries,
+            self.stats.elapsed / 1_000
+        );
+        json
+    }
+}
+
+/// Returns `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Prints `progress` to stdout. Packets are drawn as `#` and retries as `@`,
+/// or, when the total size is known, as a percentage that is only redrawn when
+/// it changes from `last_percent`.
+fn print_progress(progress: Progress, last_percent: &mut Option<usize>) {
+    match progress {
+        Progress::Waiting => println!("Waiting for receiver"),
+        Progress::Started => println!("Transmission started:"),
+        Progress::Packet { stats, .. } => match stats.total {
+            Some(total) if total > 0 => {
+                let percent = 100 * stats.bytes.min(total) / total;
+                if *last_percent != Some(percent) {
+                    *last_percent = Some(percent);
+                    let bytes = stats.bytes;
+                    print!("\r{:3}% ({}/{} bytes)", percent, bytes, total);
+                }
+            }
+            _ => print!("#"),
+        },
+        Progress::Retry { reason, .. } => match last_percent.is_some() {
+            true => print!(" retrying ({:?})", reason),
+            false => print!("@"),
+        },
+        Progress::Cancelled { reason, .. } => {
+            println!("");
+            println!("| cancelled: {:?}", reason);
+        }
+        Progress::Terminated { stats } => {
+            println!("");
+            println!(
+                "| wrote {} packets ({} bytes, {} retries) in {}.{:03}s",
+                stats.packets,
+                stats.bytes,
+                stats.retries,
+                stats.elapsed / 1_000_000,
+                stats.elapsed / 1_000 % 1_000
+            );
+        }
+    }
+
+    io::stdout().flush().unwrap();
+}
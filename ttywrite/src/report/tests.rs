@@ -0,0 +1,85 @@
+use super::*;
+use std::io::ErrorKind::NotFound;
+use std::path::PathBuf;
+
+use serial;
+
+fn reporter(stats: Stats) -> Reporter {
+    Reporter {
+        stats,
+        ..Reporter::new(true)
+    }
+}
+
+#[test]
+fn test_json_string() {
+    assert_eq!(json_string("plain"), "\"plain\"");
+    assert_eq!(json_string("a \"b\"\\c"), "\"a \\\"b\\\"\\\\c\"");
+    assert_eq!(json_string("1\n2\t\x01"), "\"1\\n2\\t\\u0001\"");
+}
+
+#[test]
+fn test_summary_ok() {
+    let mut reporter = Reporter::new(true);
+    let stats = Stats {
+        bytes: 256,
+        total: Some(200),
+        packets: 2,
+        retries: 1,
+        elapsed: 1_234_567,
+    };
+
+    reporter.progress(Progress::Terminated { stats });
+    assert_eq!(
+        reporter.summary(&Ok(200)),
+        "{\"status\":\"ok\",\"exit_code\":0,\"bytes\":200,\"packets\":2,\
+         \"retries\":1,\"elapsed_ms\":1234}"
+    );
+}
+
+#[test]
+fn test_summary_error() {
+    let reporter = reporter(Stats {
+        bytes: 128,
+        packets: 1,
+        ..Stats::default()
+    });
+
+    let e = io::Error::new(io::ErrorKind::TimedOut, "no \"NAK\"");
+    assert_eq!(
+        reporter.summary(&Err(Error::from(e))),
+        "{\"status\":\"error\",\"exit_code\":4,\"error\":\"timeout\",\
+         \"message\":\"timed out waiting for the device: no \\\"NAK\\\"\",\
+         \"bytes\":128,\"packets\":1,\"retries\":0,\"elapsed_ms\":0}"
+    );
+}
+
+#[test]
+fn test_error_classification() {
+    let io_error = |kind| io::Error::new(kind, "failed");
+    let serial_error = || {
+        serial::Error::new(serial::ErrorKind::NoDevice, "no such device")
+    };
+
+    let errors = vec![
+        (Error::Open(PathBuf::from("/dev/ttyUSB0"), serial_error()), 2),
+        (Error::Settings(serial_error()), 3),
+        (io_error(io::ErrorKind::TimedOut).into(), 4),
+        (io_error(io::ErrorKind::WouldBlock).into(), 4),
+        (io_error(io::ErrorKind::ConnectionAborted).into(), 5),
+        (io_error(io::ErrorKind::Interrupted).into(), 6),
+        (io_error(io::ErrorKind::InvalidData).into(), 6),
+        (Error::File(PathBuf::from("a.bin"), io_error(NotFound)), 6),
+    ];
+
+    for (error, code) in errors {
+        assert_eq!(error.exit_code(), code, "{}", error);
+    }
+
+    let open = Error::Open(PathBuf::from("/dev/ttyUSB0"), serial_error());
+    assert_eq!(open.name(), "open");
+    assert_eq!(
+        open.to_string(),
+        "failed to open /dev/ttyUSB0: no such device"
+    );
+}
@@ -0,0 +1,234 @@
+//! Uploading the input file again whenever it changes.
+//!
+//! After every change, the new image is sent as soon as the receiver asks for
+//! it, typically after resetting the Pi: with XMODEM once the bootloader has
+//! sent `NAK` or `C` twice, or with ZMODEM once the receiver sends `ZRINIT`.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serial::SerialPort;
+use xmodem::{Progress, Stats, Xmodem};
+
+use error::is_timeout;
+use zmodem::{self, Poll};
+use {file_name, Protocol};
+
+#[cfg(test)]
+mod tests;
+
+const NAK: u8 = 0x15;
+const CRC: u8 = b'C';
+const ZPAD: u8 = b'*';
+
+/// How often the input file is checked for changes, in milliseconds.
+const POLL_MS: u64 = 250;
+
+/// A version of a file, identified by its modification time and length.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Stamp {
+    modified: SystemTime,
+    len:      u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> io::Result<Stamp> {
+        let metadata = fs::metadata(path)?;
+        Ok(Stamp {
+            modified: metadata.modified()?,
+            len:      metadata.len(),
+        })
+    }
+}
+
+/// Returns the bytes a receiver using `protocol` starts a transfer with: `NAK`
+/// for XMODEM or `C` for XMODEM-CRC and XMODEM-1K, and the `ZPAD` that begins
+/// the `ZRINIT` header for ZMODEM.
+fn start_bytes(protocol: Protocol) -> &'static [u8] {
+    match protocol {
+        Protocol::Xmodem => &[NAK, CRC],
+        Protocol::Zmodem => &[ZPAD],
+    }
+}
+
+/// Reads from `port` until one of `start` arrives twice in a row, skipping
+/// any other bytes and read timeouts. Returns the byte that arrived.
+///
+/// A single start byte may just be part of the device's output. Receivers
+/// send theirs again after each of their timeouts, though, with only read
+/// timeouts in between, and `ZRINIT` begins with two `ZPAD`s.
+///
+/// # Errors
+///
+/// Returns an error of kind `UnexpectedEof` if `port` reaches its end, and
+/// any other error from reading `port`.
+fn wait_for_start<T: Read + ?Sized>(
+    port: &mut T,
+    start: &[u8],
+) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    let mut last = None;
+    loop {
+        match port.read(&mut byte) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "port closed while waiting for the receiver",
+                ))
+            }
+            Ok(_) if last == Some(byte[0]) && start.contains(&byte[0]) => {
+                return Ok(byte[0])
+            }
+            Ok(_) => last = Some(byte[0]),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(ref e) if is_timeout(e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A port whose first read yields the `NAK` or `C` consumed by
+/// `wait_for_start`, so that it starts the XMODEM session with the checksum
+/// the receiver asked for.
+struct Replay<'a, T: 'a + ?Sized> {
+    port:  &'a mut T,
+    start: Option<u8>,
+}
+
+impl<'a, T: 'a + ?Sized> Replay<'a, T> {
+    fn new(port: &'a mut T, start: u8) -> Replay<'a, T> {
+        Replay {
+            port,
+            start: Some(start),
+        }
+    }
+}
+
+impl<'a, T: Read + ?Sized> Read for Replay<'a, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let (Some(start), false) = (self.start, buf.is_empty()) {
+            self.start = None;
+            buf[0] = start;
+            return Ok(1);
+        }
+
+        self.port.read(buf)
+    }
+}
+
+impl<'a, T: Write + ?Sized> Write for Replay<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.port.flush() }
+}
+
+/// Discards whatever `port` received so far, such as output of the previous
+/// image or `NAK`s sent by the bootloader long ago.
+fn drain(port: &mut SerialPort, timeout: Duration) -> io::Result<()> {
+    port.set_timeout(Duration::from_millis(10))?;
+    let mut buf = [0u8; 256];
+    let result = loop {
+        match port.read(&mut buf) {
+            Ok(n) if n > 0 => {}
+            Ok(_) => break Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(ref e) if is_timeout(e) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+
+    port.set_timeout(timeout)?;
+    result
+}
+
+/// Waits for the receiver to start a transfer and sends the file at `path` to
+/// it using `protocol`. Returns the statistics of the transfer.
+fn upload<T: Poll + ?Sized>(
+    path: &Path,
+    port: &mut T,
+    protocol: Protocol,
+) -> io::Result<Stats> {
+    let start = wait_for_start(port, start_bytes(protocol))?;
+
+    let file = File::open(path)?;
+    let mut summary = Stats::default();
+    {
+        let progress = |p| {
+            if let Progress::Terminated { stats } = p {
+                summary = stats;
+            }
+        };
+
+        // The rest of the `ZRINIT` header is left for the ZMODEM sender.
+        match protocol {
+            Protocol::Xmodem => {
+                let port = Replay::new(port, start);
+                let file = BufReader::new(file);
+                Xmodem::transmit_with_progress(file, port, progress)?
+            }
+            Protocol::Zmodem => {
+                zmodem::send(&file_name(path), file, port, progress)?
+            }
+        };
+    }
+
+    Ok(summary)
+}
+
+/// Watches the file at `path`, uploading it to `port` with `protocol` after
+/// every change. Reads from `port` otherwise time out after `timeout`. Never
+/// returns unless the file can't be watched.
+///
+/// # Errors
+///
+/// Returns an error if the metadata of the file can't be read initially or
+/// the timeout of `port` can't be set. Failed uploads are reported and
+/// don't end watching.
+pub fn watch(
+    path: &Path,
+    port: &mut SerialPort,
+    protocol: Protocol,
+    timeout: Duration,
+) -> io::Result<()> {
+    let mut stamp = Stamp::of(path)?;
+    let mut uploads = 0;
+    println!("Watching {} for changes", path.display());
+    loop {
+        thread::sleep(Duration::from_millis(POLL_MS));
+        let current = match Stamp::of(path) {
+            Ok(current) if current != stamp => current,
+            // Unchanged, or in the middle of being replaced.
+            _ => continue,
+        };
+
+        // Wait for the file to stop changing before sending it.
+        thread::sleep(Duration::from_millis(POLL_MS));
+        if Stamp::of(path).ok() != Some(current) {
+            continue;
+        }
+
+        stamp = current;
+        uploads += 1;
+        println!("#{}: changed; waiting for the receiver (reset the Pi)",
+                 uploads);
+
+        drain(port, timeout)?;
+        match upload(path, port, protocol) {
+            Ok(stats) => println!(
+                "#{}: sent {} bytes in {} packets ({} retries) in {}.{:03}s",
+                uploads,
+                stats.bytes,
+                stats.packets,
+                stats.retries,
+                stats.elapsed / 1_000_000,
+                stats.elapsed / 1_000 % 1_000
+            ),
+            Err(e) => println!("#{}: error: {}", uploads, e),
+        }
+    }
+}
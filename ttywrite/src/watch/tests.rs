@@ -0,0 +1,158 @@
+use super::*;
+use std::collections::VecDeque;
+use std::env;
+use std::path::PathBuf;
+
+/// A port replaying scripted reads, one byte or error at a time, and
+/// recording what is written to it.
+struct Script(VecDeque<io::Result<u8>>, Vec<u8>);
+
+fn script(reads: Vec<io::Result<u8>>) -> Script {
+    Script(reads.into_iter().collect(), vec![])
+}
+
+/// Returns successful reads of each of `bytes`.
+fn bytes(bytes: &[u8]) -> Vec<io::Result<u8>> {
+    bytes.iter().map(|&byte| Ok(byte)).collect()
+}
+
+fn timeout() -> io::Result<u8> {
+    Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+}
+
+impl io::Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.pop_front() {
+            Some(Ok(byte)) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            Some(Err(e)) => Err(e),
+            None => Ok(0),
+        }
+    }
+}
+
+impl io::Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.1.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl Poll for Script {
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.front() {
+            Some(&Ok(_)) => io::Read::read(self, buf),
+            _ => Ok(0),
+        }
+    }
+}
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("ttywrite-watch-{}", name));
+    File::create(&path)
+        .and_then(|mut file| file.write_all(contents))
+        .expect("write temp file");
+    path
+}
+
+#[test]
+fn test_wait_for_start() {
+    let mut port = script(vec![
+        Ok(b'h'),
+        timeout(),
+        Ok(NAK),
+        timeout(),
+        Ok(NAK),
+        Ok(b'x'),
+    ]);
+    let xmodem = start_bytes(Protocol::Xmodem);
+    assert_eq!(wait_for_start(&mut port, xmodem).expect("NAK"), NAK);
+
+    {
+        let mut replay = Replay::new(&mut port, NAK);
+        let mut buf = [0u8; 2];
+        assert_eq!(replay.read(&mut buf).expect("replayed NAK"), 1);
+        assert_eq!(buf[0], NAK);
+        assert_eq!(replay.read(&mut buf).expect("next byte"), 1);
+        assert_eq!(buf[0], b'x');
+    }
+
+    let e = wait_for_start(&mut port, xmodem).expect_err("closed");
+    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut port = script(bytes(&[b'o', CRC, CRC, ZPAD, ZPAD]));
+    assert_eq!(wait_for_start(&mut port, xmodem).expect("C"), CRC);
+    let zmodem = start_bytes(Protocol::Zmodem);
+    assert_eq!(wait_for_start(&mut port, zmodem).expect("ZPAD"), ZPAD);
+}
+
+#[test]
+fn test_wait_for_start_ignores_output() {
+    let xmodem = start_bytes(Protocol::Xmodem);
+    let mut reads = bytes(b"Calling kmain\r\n");
+    reads.extend(vec![timeout(), Ok(CRC), Ok(b'P'), Ok(b'U'), Ok(CRC)]);
+    reads.extend(vec![Ok(NAK), timeout(), Ok(b'\n'), Ok(NAK)]);
+    let mut port = script(reads);
+
+    let e = wait_for_start(&mut port, xmodem).expect_err("no start");
+    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+    let path = temp_file("output", &[0x42; 100]);
+    let mut port = script(bytes(b"C\r\n"));
+    let e = upload(&path, &mut port, Protocol::Xmodem).expect_err("no upload");
+    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(port.1.is_empty());
+}
+
+#[test]
+fn test_upload_after_nak() {
+    let path = temp_file("upload", &[0x42; 100]);
+    let mut port = script(vec![
+        Ok(b'b'),
+        Ok(b'o'),
+        timeout(),
+        Ok(NAK),
+        timeout(),
+        Ok(NAK),
+        Ok(0x06),
+        Ok(NAK),
+        Ok(0x06),
+    ]);
+
+    let stats = upload(&path, &mut port, Protocol::Xmodem).expect("upload");
+    assert_eq!(stats.packets, 1);
+    assert_eq!(stats.bytes, 128);
+    assert_eq!(port.1.len(), 132 + 2);
+    assert_eq!(&port.1[..3], &[0x01, 1, 254]);
+    assert_eq!(&port.1[3..103], &[0x42; 100][..]);
+}
+
+#[test]
+fn test_upload_after_crc_request() {
+    let path = temp_file("upload-crc", &[0x42; 100]);
+    let mut port =
+        script(vec![Ok(CRC), Ok(CRC), Ok(0x06), Ok(NAK), Ok(0x06)]);
+
+    let stats = upload(&path, &mut port, Protocol::Xmodem).expect("upload");
+    assert_eq!(stats.packets, 1);
+
+    // The packet carries a two byte CRC-16 rather than a checksum.
+    assert_eq!(port.1.len(), 133 + 2);
+    assert_eq!(&port.1[..3], &[0x01, 1, 254]);
+}
+
+#[test]
+fn test_stamp_changes() {
+    let path = temp_file("stamp", b"a");
+    let before = Stamp::of(&path).expect("stamp");
+    assert_eq!(Stamp::of(&path).expect("stamp"), before);
+
+    File::create(&path)
+        .and_then(|mut file| file.write_all(b"ab"))
+        .expect("rewrite temp file");
+    assert!(Stamp::of(&path).expect("stamp") != before);
+}
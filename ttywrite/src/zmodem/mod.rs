@@ -0,0 +1,931 @@
+//! A streaming ZMODEM sender.
+//!
+//! Unlike XMODEM, ZMODEM does not wait for every block to be acknowledged:
+//! file data is streamed as a sequence of data subpackets, each protected by
+//! a CRC-32 (or a CRC-16 if the receiver can't check CRC-32), and the
+//! receiver only interrupts the stream with a `ZRPOS` header naming the
+//! offset to continue from when a subpacket is damaged. The same header lets
+//! a receiver that already holds part of a file resume the transfer.
+//!
+//! Only what `ttywrite` needs is implemented: a single file is sent per
+//! session and the receiver's file management options are ignored.
+
+use std::cmp;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+
+use serial::SerialPort;
+use xmodem::{CancelReason, Progress, RetryReason, Stats};
+
+use error::is_timeout;
+
+#[cfg(test)]
+mod tests;
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18;
+const CAN: u8 = 0x18;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+// Header formats.
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+
+// Header (frame) types.
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCAN: u8 = 16;
+
+// Data subpacket ends.
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// `ZRINIT` capability flags.
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+
+/// `ZFILE` conversion option: binary transfer.
+const ZCBIN: u8 = 1;
+
+/// The largest amount of data sent in a single data subpacket.
+const BLOCK: usize = 1024;
+
+/// The amount of data streamed before waiting for the receiver to
+/// acknowledge it, bounding how much is resent after an error.
+const WINDOW: usize = 16 * 1024;
+
+/// The amount of data streamed between requests for an acknowledgement when
+/// the receiver can acknowledge data while receiving it.
+const ACK_INTERVAL: usize = 4 * 1024;
+
+/// The number of times a header is sent, or data is resent, without progress
+/// before the session is cancelled.
+const MAX_RETRIES: usize = 10;
+
+/// The number of bytes skipped while looking for a header before giving up.
+const MAX_GARBAGE: usize = 2048;
+
+/// The abort sequence: ten `CAN` followed by ten backspaces.
+const ABORT: [u8; 20] = [
+    CAN, CAN, CAN, CAN, CAN, CAN, CAN, CAN, CAN, CAN, 8, 8, 8, 8, 8, 8, 8, 8,
+    8, 8,
+];
+
+/// A connection that can be read from without waiting for input.
+pub trait Poll: Read + Write {
+    /// Reads whatever input has already arrived into `buf` without waiting
+    /// for more. Returns `0` if there is none.
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl<'a, T: Poll + ?Sized> Poll for &'a mut T {
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_available(buf)
+    }
+}
+
+impl<'a> Poll for SerialPort + 'a {
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = self.timeout();
+        self.set_timeout(Duration::from_millis(0))?;
+        let result = self.read(buf);
+        self.set_timeout(timeout)?;
+        match result {
+            Err(ref e) if is_timeout(e) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+/// Sends `data` as a file called `name` to `to` using the ZMODEM protocol.
+/// Returns the number of bytes sent, which is less than the length of `data`
+/// if the receiver resumed the transfer from an offset.
+///
+/// The function `f` is used as a callback to indicate progress throughout the
+/// transfer. See [`Sender::send()`] for details.
+///
+/// # Errors
+///
+/// Returns an error if reading from `data` or reading or writing to `to`
+/// fails, or if the ZMODEM protocol indicates an error.
+pub fn send<R, T, F>(name: &str, data: R, to: T, f: F) -> io::Result<usize>
+where
+    R: Read + Seek,
+    T: Poll,
+    F: FnMut(Progress),
+{
+    Sender::new(to, f).send(name, data)
+}
+
+/// A checksum of a header or data subpacket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Crc {
+    /// The CRC-16 used by XMODEM-CRC.
+    Crc16(u16),
+    /// The CRC-32 used by Ethernet.
+    Crc32(u32),
+}
+
+impl Crc {
+    /// Returns the initial CRC-32 if `crc32` is set, else the initial CRC-16.
+    fn new(crc32: bool) -> Crc {
+        match crc32 {
+            true => Crc::Crc32(0xFFFF_FFFF),
+            false => Crc::Crc16(0),
+        }
+    }
+
+    /// Returns this checksum updated with `bytes`.
+    fn update(self, bytes: &[u8]) -> Crc {
+        match self {
+            Crc::Crc16(mut crc) => {
+                for byte in bytes {
+                    crc ^= (*byte as u16) << 8;
+                    for _ in 0..8 {
+                        crc = match crc & 0x8000 {
+                            0 => crc << 1,
+                            _ => crc << 1 ^ 0x1021,
+                        };
+                    }
+                }
+
+                Crc::Crc16(crc)
+            }
+            Crc::Crc32(mut crc) => {
+                for byte in bytes {
+                    crc ^= *byte as u32;
+                    for _ in 0..8 {
+                        crc = match crc & 1 {
+                            0 => crc >> 1,
+                            _ => crc >> 1 ^ 0xEDB8_8320,
+                        };
+                    }
+                }
+
+                Crc::Crc32(crc)
+            }
+        }
+    }
+
+    /// Returns the final checksum in the order it is sent: a CRC-16 most
+    /// significant byte first, a CRC-32 least significant byte first.
+    fn bytes(self) -> Vec<u8> {
+        match self {
+            Crc::Crc16(crc) => vec![(crc >> 8) as u8, crc as u8],
+            Crc::Crc32(crc) => {
+                let crc = !crc;
+                vec![
+                    crc as u8,
+                    (crc >> 8) as u8,
+                    (crc >> 16) as u8,
+                    (crc >> 24) as u8,
+                ]
+            }
+        }
+    }
+}
+
+/// A ZMODEM header: a frame type followed by four bytes holding either a file
+/// position, least significant byte first, or flags, with `ZF0` last.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Header {
+    kind: u8,
+    data: [u8; 4],
+}
+
+impl Header {
+    fn new(kind: u8, data: [u8; 4]) -> Header { Header { kind, data } }
+
+    fn with_position(kind: u8, pos: u32) -> Header {
+        let data = [
+            pos as u8,
+            (pos >> 8) as u8,
+            (pos >> 16) as u8,
+            (pos >> 24) as u8,
+        ];
+        Header::new(kind, data)
+    }
+
+    /// Returns the file position held by this header.
+    fn position(&self) -> u32 {
+        self.data.iter().rev().fold(0, |pos, b| pos << 8 | *b as u32)
+    }
+
+    /// Returns the `ZF0` flags held by this header.
+    fn flags(&self) -> u8 { self.data[3] }
+
+    fn bytes(&self) -> [u8; 5] {
+        [self.kind, self.data[0], self.data[1], self.data[2], self.data[3]]
+    }
+}
+
+/// A byte of ZDLE-decoded input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Escaped {
+    /// A data byte.
+    Byte(u8),
+    /// The end of a data subpacket: `ZCRCE`, `ZCRCG`, `ZCRCQ` or `ZCRCW`.
+    End(u8),
+}
+
+/// Appends `bytes` to `out`, escaping those that may not be sent as is.
+fn escape(out: &mut Vec<u8>, bytes: &[u8]) {
+    for byte in bytes {
+        match *byte {
+            ZDLE | 0x10 | 0x90 | XON | 0x91 | XOFF | 0x93 => {
+                out.push(ZDLE);
+                out.push(*byte ^ 0x40);
+            }
+            byte => out.push(byte),
+        }
+    }
+}
+
+/// ZMODEM framing over a reader/writer: headers in both directions and data
+/// subpackets.
+struct Link<T> {
+    inner:   T,
+    /// Whether binary headers and data subpackets are protected by a CRC-32
+    /// rather than a CRC-16.
+    crc32:   bool,
+    /// A byte read by `poll()` that hasn't been consumed yet.
+    pending: Option<u8>,
+}
+
+impl<T: Read + Write> Link<T> {
+    fn new(inner: T) -> Link<T> {
+        Link {
+            inner,
+            crc32: false,
+            pending: None,
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(byte);
+        }
+
+        let mut byte = [0u8; 1];
+        self.inner.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Reads and decodes a single, possibly escaped, byte. Flow control
+    /// characters are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `ConnectionAborted` if five `CAN` bytes are
+    /// received and an error of kind `InvalidData` for an invalid escape.
+    fn read_escaped(&mut self) -> io::Result<Escaped> {
+        let mut cans = 0;
+        loop {
+            let byte = self.read_byte()?;
+            match (cans, byte) {
+                (_, XON) | (_, 0x91) | (_, XOFF) | (_, 0x93) => {}
+                (_, ZDLE) => {
+                    cans += 1;
+                    if cans >= 5 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::ConnectionAborted,
+                            "received CAN",
+                        ));
+                    }
+                }
+                (0, byte) => return Ok(Escaped::Byte(byte)),
+                (_, ZCRCE) | (_, ZCRCG) | (_, ZCRCQ) | (_, ZCRCW) => {
+                    return Ok(Escaped::End(byte))
+                }
+                (_, ZRUB0) => return Ok(Escaped::Byte(0x7F)),
+                (_, ZRUB1) => return Ok(Escaped::Byte(0xFF)),
+                (_, byte) if byte & 0x60 == 0x40 => {
+                    return Ok(Escaped::Byte(byte ^ 0x40))
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid escape sequence",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Reads a single escaped data byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if a subpacket ends instead.
+    fn read_data_byte(&mut self) -> io::Result<u8> {
+        match self.read_escaped()? {
+            Escaped::Byte(byte) => Ok(byte),
+            Escaped::End(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected end of subpacket",
+            )),
+        }
+    }
+
+    /// Skips input until a header is found and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if no header is found within
+    /// `MAX_GARBAGE` bytes or the header's CRC doesn't match, and an error of
+    /// kind `ConnectionAborted` if five `CAN` bytes are received.
+    fn read_header(&mut self) -> io::Result<Header> {
+        let (mut garbage, mut cans) = (0, 0);
+        loop {
+            let byte = self.read_byte()?;
+            if byte == CAN {
+                cans += 1;
+                if cans >= 5 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "received CAN",
+                    ));
+                }
+
+                continue;
+            }
+
+            cans = 0;
+            if byte != ZPAD {
+                garbage += 1;
+                if garbage > MAX_GARBAGE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "no header found",
+                    ));
+                }
+
+                continue;
+            }
+
+            let mut byte = self.read_byte()?;
+            while byte == ZPAD {
+                byte = self.read_byte()?;
+            }
+
+            if byte != ZDLE {
+                continue;
+            }
+
+            match self.read_byte()? {
+                ZHEX => return self.read_hex_header(),
+                ZBIN => return self.read_bin_header(false),
+                ZBIN32 => return self.read_bin_header(true),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_hex_header(&mut self) -> io::Result<Header> {
+        let mut bytes = [0u8; 7];
+        for byte in bytes.iter_mut() {
+            *byte = self.read_hex_digit()? << 4 | self.read_hex_digit()?;
+        }
+
+        // Any trailing XON is skipped while looking for the next header.
+        if self.read_byte()? == b'\r' {
+            self.read_byte()?;
+        }
+
+        if Crc::new(false).update(&bytes[..5]).bytes() != &bytes[5..] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "header CRC mismatch",
+            ));
+        }
+
+        Ok(Header::new(bytes[0], [bytes[1], bytes[2], bytes[3], bytes[4]]))
+    }
+
+    fn read_hex_digit(&mut self) -> io::Result<u8> {
+        match (self.read_byte()? as char).to_digit(16) {
+            Some(digit) => Ok(digit as u8),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid hex header",
+            )),
+        }
+    }
+
+    fn read_bin_header(&mut self, crc32: bool) -> io::Result<Header> {
+        let mut bytes = [0u8; 5];
+        for byte in bytes.iter_mut() {
+            *byte = self.read_data_byte()?;
+        }
+
+        for expected in Crc::new(crc32).update(&bytes).bytes() {
+            if self.read_data_byte()? != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "header CRC mismatch",
+                ));
+            }
+        }
+
+        Ok(Header::new(bytes[0], [bytes[1], bytes[2], bytes[3], bytes[4]]))
+    }
+
+    /// Writes `header` as a hex header, which is always protected by a
+    /// CRC-16.
+    fn write_hex_header(&mut self, header: &Header) -> io::Result<()> {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        let bytes = header.bytes();
+        let crc = Crc::new(false).update(&bytes).bytes();
+        let mut out = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+        for byte in bytes.iter().chain(&crc) {
+            out.push(HEX[(*byte >> 4) as usize]);
+            out.push(HEX[(*byte & 0xF) as usize]);
+        }
+
+        out.extend_from_slice(&[b'\r', b'\n' | 0x80]);
+        if header.kind != ZACK && header.kind != ZFIN {
+            out.push(XON);
+        }
+
+        self.inner.write_all(&out)?;
+        self.inner.flush()
+    }
+
+    /// Writes `header` as a binary header.
+    fn write_bin_header(&mut self, header: &Header) -> io::Result<()> {
+        let format = if self.crc32 { ZBIN32 } else { ZBIN };
+        let bytes = header.bytes();
+        let mut out = vec![ZPAD, ZDLE, format];
+        escape(&mut out, &bytes);
+        escape(&mut out, &Crc::new(self.crc32).update(&bytes).bytes());
+
+        self.inner.write_all(&out)?;
+        self.inner.flush()
+    }
+
+    /// Writes `data` as a data subpacket ending with `end`.
+    fn write_subpacket(&mut self, data: &[u8], end: u8) -> io::Result<()> {
+        let crc = Crc::new(self.crc32).update(data).update(&[end]);
+        let mut out = Vec::with_capacity(2 * data.len() + 12);
+        escape(&mut out, data);
+        out.extend_from_slice(&[ZDLE, end]);
+        escape(&mut out, &crc.bytes());
+        if end == ZCRCW {
+            out.push(XON);
+        }
+
+        self.inner.write_all(&out)?;
+        self.inner.flush()
+    }
+}
+
+impl<T: Poll> Link<T> {
+    /// Returns `true` if input other than flow control characters, such as
+    /// the `XON` ending a hex header, has arrived, without waiting for any.
+    fn poll(&mut self) -> io::Result<bool> {
+        let mut byte = [0u8; 1];
+        while self.pending.is_none() {
+            if self.inner.read_available(&mut byte)? == 0 {
+                return Ok(false);
+            }
+
+            match byte[0] {
+                XON | 0x91 | XOFF | 0x93 => {}
+                byte => self.pending = Some(byte),
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// The sending side of a ZMODEM session.
+pub struct Sender<T, P> {
+    link:     Link<T>,
+    /// The amount of data streamed before waiting for an acknowledgement.
+    window:   usize,
+    /// Whether the receiver accepts data while acknowledging it. If not, each
+    /// window is sent as a frame of its own.
+    overlap:  bool,
+    started:  Option<Instant>,
+    stats:    Stats,
+    progress: P,
+}
+
+impl<T: Poll, P: FnMut(Progress)> Sender<T, P> {
+    /// Returns a new `Sender` writing to and reading from `inner`. The
+    /// function `f` is used as a callback to indicate progress.
+    pub fn new(inner: T, f: P) -> Self {
+        Sender {
+            link:     Link::new(inner),
+            window:   WINDOW,
+            overlap:  true,
+            started:  None,
+            stats:    Stats::default(),
+            progress: f,
+        }
+    }
+
+    /// Sends `data` as a file called `name` and ends the session. Returns the
+    /// number of bytes sent, which is less than the length of `data` if the
+    /// receiver resumed the transfer from an offset.
+    ///
+    /// Data is streamed in subpackets of up to 1024 bytes. If the receiver
+    /// can acknowledge data while receiving it, an acknowledgement is
+    /// requested every 4KiB and checked for between subpackets without
+    /// waiting; the sender only waits once 16KiB are unacknowledged.
+    /// Otherwise, the sender waits for the receiver to acknowledge the data
+    /// every 16KiB, or every buffer's worth if the receiver announces a
+    /// buffer size. Whenever the receiver asks for data from an offset with
+    /// `ZRPOS`, sending continues from that offset.
+    ///
+    /// The progress callback is called with `Progress::Waiting` before waiting
+    /// for the receiver, `Progress::Started` once it responds, and then with
+    /// `Progress::Packet` for every data subpacket sent, `Progress::Retry`
+    /// whenever data or a header is sent again and `Progress::Terminated` when
+    /// the session ends. The `bytes` of the reported `Stats` are the offset
+    /// into `data` reached so far. `Progress::Cancelled` is reported whenever
+    /// the session is cancelled by either side.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if seeking or reading `data` or reading or writing to
+    /// the inner stream fails at any point. In particular:
+    ///
+    ///   * An error of kind `InvalidInput` is returned if `data` is larger
+    ///     than 4GiB.
+    ///   * An error of kind `TimedOut` is returned if the receiver never
+    ///     responds.
+    ///   * An error of kind `ConnectionAborted` is returned if the receiver
+    ///     cancels the session.
+    ///   * An error of kind `BrokenPipe` is returned and the session is
+    ///     cancelled if a header or data is sent `MAX_RETRIES` times without
+    ///     progress.
+    ///   * An error of kind `InvalidData` is returned and the session is
+    ///     cancelled if the receiver asks for an offset past the end of
+    ///     `data`.
+    pub fn send<R>(&mut self, name: &str, mut data: R) -> io::Result<usize>
+    where
+        R: Read + Seek,
+    {
+        let len = data.seek(SeekFrom::End(0))?;
+        if len > u32::max_value() as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file too large for ZMODEM",
+            ));
+        }
+
+        let len = len as u32;
+        self.stats = Stats {
+            total: Some(len as usize),
+            ..Stats::default()
+        };
+
+        self.handshake()?;
+        let sent = match self.send_file_header(name, len)? {
+            Some(start) => self.send_file_data(&mut data, start, len)?,
+            None => 0,
+        };
+
+        self.finish()?;
+        Ok(sent)
+    }
+
+    /// Announces the session with `ZRQINIT` until the receiver responds with
+    /// `ZRINIT`, and adopts the receiver's capabilities.
+    fn handshake(&mut self) -> io::Result<()> {
+        (self.progress)(Progress::Waiting);
+        self.link.inner.write_all(b"rz\r")?;
+        for _ in 0..MAX_RETRIES {
+            self.link.write_hex_header(&Header::new(ZRQINIT, [0; 4]))?;
+            match self.read_header()? {
+                Some(ref header) if header.kind == ZRINIT => {
+                    let flags = header.flags();
+                    let buffer = header.data[0] as usize
+                        | (header.data[1] as usize) << 8;
+
+                    self.link.crc32 = flags & CANFC32 != 0;
+                    self.overlap = flags & CANFDX != 0 && flags & CANOVIO != 0;
+                    if buffer != 0 {
+                        self.window = cmp::min(buffer, WINDOW);
+                        self.overlap = false;
+                    }
+
+                    self.started = Some(Instant::now());
+                    (self.progress)(Progress::Started);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "receiver did not respond",
+        ))
+    }
+
+    /// Sends the file's name and length until the receiver responds with the
+    /// offset to start from. Returns `None` if the receiver skips the file.
+    fn send_file_header(
+        &mut self,
+        name: &str,
+        len: u32,
+    ) -> io::Result<Option<u32>> {
+        let info = format!("{}\0{}\0", name, len);
+        let mut resend = true;
+        for _ in 0..MAX_RETRIES {
+            if resend {
+                let header = Header::new(ZFILE, [0, 0, 0, ZCBIN]);
+                self.link.write_bin_header(&header)?;
+                self.link.write_subpacket(info.as_bytes(), ZCRCW)?;
+            }
+
+            // A `ZRINIT` that crossed the `ZFILE` header is stale.
+            resend = true;
+            match self.read_header()? {
+                Some(ref header) if header.kind == ZRPOS => {
+                    return self.check_position(header, len).map(Some)
+                }
+                Some(ref header) if header.kind == ZSKIP => return Ok(None),
+                Some(ref header) if header.kind == ZRINIT => resend = false,
+                Some(ref header) if header.kind == ZNAK => {
+                    self.retry(RetryReason::Nak)
+                }
+                _ => self.retry(RetryReason::Timeout),
+            }
+        }
+
+        Err(self.cancel("file header not acknowledged"))
+    }
+
+    /// Streams `data` from offset `start` followed by `ZEOF`, continuing from
+    /// wherever the receiver asks, until the receiver acknowledges `ZEOF`.
+    /// Returns the number of bytes sent.
+    fn send_file_data<R: Read + Seek>(
+        &mut self,
+        data: &mut R,
+        start: u32,
+        len: u32,
+    ) -> io::Result<usize> {
+        let mut buf = [0u8; BLOCK];
+        let (mut offset, mut acked, mut attempts) = (start, start, 0);
+        'frame: loop {
+            if attempts >= MAX_RETRIES {
+                return Err(self.cancel("too many retries"));
+            }
+
+            if offset < len {
+                data.seek(SeekFrom::Start(offset as u64))?;
+                let header = Header::with_position(ZDATA, offset);
+                self.link.write_bin_header(&header)?;
+            }
+
+            let mut unqueried = 0;
+            while offset < len {
+                let unacked = (offset - acked) as usize;
+                let n = cmp::min(
+                    cmp::min(BLOCK, self.window - unacked),
+                    (len - offset) as usize,
+                );
+                data.read_exact(&mut buf[..n])?;
+                offset += n as u32;
+                unqueried += n;
+
+                let query = self.overlap && unqueried >= ACK_INTERVAL;
+                let end = match (offset == len, unacked + n == self.window) {
+                    (true, _) => ZCRCE,
+                    (false, true) => ZCRCW,
+                    (false, false) if query => ZCRCQ,
+                    (false, false) => ZCRCG,
+                };
+
+                if end == ZCRCQ {
+                    unqueried = 0;
+                }
+
+                self.link.write_subpacket(&buf[..n], end)?;
+                self.stats.bytes = offset as usize;
+                self.stats.packets += 1;
+                let stats = self.stats();
+                (self.progress)(Progress::Packet {
+                    packet: stats.packets as u8,
+                    size: n,
+                    stats,
+                });
+
+                // Acknowledgements of earlier `ZCRCQ` subpackets may arrive
+                // before the one for this `ZCRCW`.
+                while end == ZCRCW {
+                    match self.read_header()? {
+                        Some(ref header)
+                            if header.kind == ZACK
+                                && header.position() < offset =>
+                        {
+                            acked = cmp::max(acked, header.position());
+                        }
+                        Some(ref header) if header.kind == ZACK => {
+                            acked = offset;
+                            attempts = 0;
+                            continue 'frame;
+                        }
+                        Some(ref header) if header.kind == ZRPOS => {
+                            offset = self.check_position(header, len)?;
+                            acked = offset;
+                            attempts += 1;
+                            self.retry(RetryReason::Nak);
+                            continue 'frame;
+                        }
+                        _ => {
+                            offset = acked;
+                            attempts += 1;
+                            self.retry(RetryReason::Timeout);
+                            continue 'frame;
+                        }
+                    }
+                }
+
+                while self.overlap && self.link.poll()? {
+                    match self.read_header()? {
+                        Some(ref header) if header.kind == ZACK => {
+                            let position = cmp::min(header.position(), offset);
+                            acked = cmp::max(acked, position);
+                            attempts = 0;
+                        }
+                        Some(ref header) if header.kind == ZRPOS => {
+                            offset = self.check_position(header, len)?;
+                            acked = offset;
+                            attempts += 1;
+                            self.retry(RetryReason::Nak);
+                            continue 'frame;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            self.link.write_bin_header(&Header::with_position(ZEOF, len))?;
+            // Acknowledgements of the last `ZCRCQ` subpackets may come first.
+            let mut header = self.read_header()?;
+            while header.map_or(false, |header| header.kind == ZACK) {
+                header = self.read_header()?;
+            }
+
+            match header {
+                Some(ref header) if header.kind == ZRINIT => {
+                    return Ok((len - start) as usize)
+                }
+                Some(ref header) if header.kind == ZSKIP => {
+                    return Ok((len - start) as usize)
+                }
+                Some(ref header) if header.kind == ZRPOS => {
+                    offset = self.check_position(header, len)?;
+                    acked = offset;
+                    self.retry(RetryReason::Nak);
+                }
+                _ => self.retry(RetryReason::Timeout),
+            }
+
+            attempts += 1;
+        }
+    }
+
+    /// Ends the session with `ZFIN` and, once the receiver answers with
+    /// `ZFIN`, "over and out".
+    fn finish(&mut self) -> io::Result<()> {
+        for _ in 0..MAX_RETRIES {
+            self.link.write_hex_header(&Header::new(ZFIN, [0; 4]))?;
+            match self.read_header()? {
+                Some(ref header) if header.kind == ZFIN => {
+                    self.link.inner.write_all(b"OO")?;
+                    self.link.inner.flush()?;
+                    let stats = self.stats();
+                    (self.progress)(Progress::Terminated { stats });
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "receiver did not acknowledge the end of the session",
+        ))
+    }
+
+    /// Reads the receiver's next header. Returns `None` if no valid header
+    /// arrives in time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `ConnectionAborted` if the receiver cancels or
+    /// aborts the session, and any error from the inner stream other than a
+    /// timeout.
+    fn read_header(&mut self) -> io::Result<Option<Header>> {
+        match self.link.read_header() {
+            Ok(header) => match header.kind {
+                ZCAN | ZABORT | ZFERR => Err(self.cancelled()),
+                _ => Ok(Some(header)),
+            },
+            Err(ref e) if is_timeout(e) => Ok(None),
+            Err(e) => match e.kind() {
+                io::ErrorKind::InvalidData => Ok(None),
+                io::ErrorKind::ConnectionAborted => Err(self.cancelled()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Returns the position requested by `header`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` and cancels the session if the
+    /// position is past `len`.
+    fn check_position(&mut self, header: &Header, len: u32) -> io::Result<u32> {
+        match header.position() {
+            pos if pos <= len => Ok(pos),
+            _ => {
+                self.cancel("invalid file position");
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "receiver requested an invalid file position",
+                ))
+            }
+        }
+    }
+
+    /// Reports that a header or data is sent again because of `reason`.
+    fn retry(&mut self, reason: RetryReason) {
+        self.stats.retries += 1;
+        let stats = self.stats();
+        (self.progress)(Progress::Retry {
+            packet: stats.packets as u8,
+            reason,
+            stats,
+        });
+    }
+
+    /// Sends the abort sequence and returns an error of kind `BrokenPipe`
+    /// described by `reason`.
+    fn cancel(&mut self, reason: &'static str) -> io::Error {
+        let stats = self.stats();
+        (self.progress)(Progress::Cancelled {
+            reason: CancelReason::Sent(reason),
+            stats,
+        });
+
+        match self.link.inner.write_all(&ABORT) {
+            Ok(_) => io::Error::new(io::ErrorKind::BrokenPipe, reason),
+            Err(e) => e,
+        }
+    }
+
+    /// Reports that the receiver cancelled the session and returns an error
+    /// of kind `ConnectionAborted`.
+    fn cancelled(&mut self) -> io::Error {
+        let stats = self.stats();
+        (self.progress)(Progress::Cancelled {
+            reason: CancelReason::Received,
+            stats,
+        });
+
+        io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            "receiver cancelled the session",
+        )
+    }
+
+    /// Returns a snapshot of the transfer statistics.
+    fn stats(&self) -> Stats {
+        let elapsed = self.started.map_or(0, |at| {
+            let elapsed = at.elapsed();
+            let micros = elapsed.subsec_nanos() as u64 / 1_000;
+            elapsed.as_secs() * 1_000_000 + micros
+        });
+
+        Stats {
+            elapsed,
+            ..self.stats
+        }
+    }
+}
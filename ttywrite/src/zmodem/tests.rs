@@ -0,0 +1,423 @@
+use super::*;
+use std::io::{Cursor, ErrorKind};
+use std::sync::mpsc::{self, channel};
+use std::thread;
+
+/// How long a read from a `Pipe` waits for input.
+const PIPE_TIMEOUT_MS: u64 = 2_000;
+
+/// One end of an in-process connection. If `corrupt` is set, the byte
+/// written at that index is damaged in transit.
+struct Pipe {
+    tx:      mpsc::Sender<u8>,
+    rx:      mpsc::Receiver<u8>,
+    written: usize,
+    corrupt: Option<usize>,
+}
+
+fn pipe(corrupt: Option<usize>) -> (Pipe, Pipe) {
+    let ((tx1, rx1), (tx2, rx2)) = (channel(), channel());
+    let a = Pipe { tx: tx1, rx: rx2, written: 0, corrupt };
+    let b = Pipe { tx: tx2, rx: rx1, written: 0, corrupt: None };
+    (a, b)
+}
+
+impl io::Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = Duration::from_millis(PIPE_TIMEOUT_MS);
+        match self.rx.recv_timeout(timeout) {
+            Ok(byte) if buf.len() > 0 => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out",
+            )),
+            _ => Ok(0),
+        }
+    }
+}
+
+impl Poll for Pipe {
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.rx.try_recv() {
+            Ok(byte) if buf.len() > 0 => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+impl io::Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            let byte = match self.corrupt {
+                Some(i) if i == self.written => *byte ^ 0x20,
+                _ => *byte,
+            };
+
+            self.written += 1;
+            if self.tx.send(byte).is_err() {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/// A minimal reference receiver, announcing `flags` and `buffer` in its
+/// `ZRINIT` and already holding `file`. If `quiet` is set, `ZCRCQ`
+/// subpackets are not acknowledged.
+struct Reference {
+    flags:  u8,
+    buffer: u16,
+    file:   Vec<u8>,
+    cancel: bool,
+    quiet:  bool,
+}
+
+impl Reference {
+    fn new(flags: u8) -> Reference {
+        Reference {
+            flags,
+            buffer: 0,
+            file: vec![],
+            cancel: false,
+            quiet: false,
+        }
+    }
+
+    /// Receives a single file, returning its name and contents.
+    fn receive(mut self, pipe: Pipe) -> io::Result<(String, Vec<u8>)> {
+        let mut link = Link::new(pipe);
+        link.crc32 = self.flags & CANFC32 != 0;
+
+        let zrinit = Header::new(
+            ZRINIT,
+            [self.buffer as u8, (self.buffer >> 8) as u8, 0, self.flags],
+        );
+        link.write_hex_header(&zrinit)?;
+
+        let mut name = String::new();
+        loop {
+            let header = match link.read_header() {
+                Ok(header) => header,
+                Err(ref e) if e.kind() == ErrorKind::InvalidData => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+
+            let pos = self.file.len() as u32;
+            match header.kind {
+                ZRQINIT => link.write_hex_header(&zrinit)?,
+                ZFILE => {
+                    let (info, _) = read_subpacket(&mut link)?;
+                    if self.cancel {
+                        link.inner.write_all(&[CAN; 5])?;
+                        return Err(io::Error::new(
+                            io::ErrorKind::ConnectionAborted,
+                            "cancelled",
+                        ));
+                    }
+
+                    let end = info.iter().position(|b| *b == 0).unwrap();
+                    name = String::from_utf8(info[..end].to_vec()).unwrap();
+                    link.write_hex_header(&Header::with_position(ZRPOS, pos))?;
+                }
+                ZDATA if header.position() != pos => {
+                    link.write_hex_header(&Header::with_position(ZRPOS, pos))?
+                }
+                ZDATA => loop {
+                    let (data, end) = match read_subpacket(&mut link) {
+                        Ok(subpacket) => subpacket,
+                        Err(ref e) if e.kind() == ErrorKind::InvalidData => {
+                            let pos = self.file.len() as u32;
+                            link.write_hex_header(&Header::with_position(
+                                ZRPOS, pos,
+                            ))?;
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    };
+
+                    self.file.extend_from_slice(&data);
+                    let pos = self.file.len() as u32;
+                    let ack = Header::with_position(ZACK, pos);
+                    match end {
+                        ZCRCQ if !self.quiet => link.write_hex_header(&ack)?,
+                        ZCRCW => {
+                            link.write_hex_header(&ack)?;
+                            break;
+                        }
+                        ZCRCE => break,
+                        _ => {}
+                    }
+                },
+                ZEOF if header.position() != pos => {
+                    link.write_hex_header(&Header::with_position(ZRPOS, pos))?
+                }
+                ZEOF => link.write_hex_header(&zrinit)?,
+                ZFIN => {
+                    link.write_hex_header(&Header::new(ZFIN, [0; 4]))?;
+                    let (o1, o2) = (link.read_byte()?, link.read_byte()?);
+                    assert_eq!((o1, o2), (b'O', b'O'));
+                    return Ok((name, self.file));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads a data subpacket, returning its data and how it ended.
+fn read_subpacket<T: Read + Write>(
+    link: &mut Link<T>,
+) -> io::Result<(Vec<u8>, u8)> {
+    let mut data = vec![];
+    let end = loop {
+        match link.read_escaped()? {
+            Escaped::Byte(byte) => data.push(byte),
+            Escaped::End(end) => break end,
+        }
+    };
+
+    for expected in Crc::new(link.crc32).update(&data).update(&[end]).bytes() {
+        if link.read_data_byte()? != expected {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad CRC"));
+        }
+    }
+
+    Ok((data, end))
+}
+
+/// Sends `data` to `reference` over a pipe damaging the byte at `corrupt`,
+/// returning the sender's and receiver's results and the progress reported.
+fn transfer(
+    data: &[u8],
+    reference: Reference,
+    corrupt: Option<usize>,
+) -> (io::Result<usize>, io::Result<(String, Vec<u8>)>, Vec<Progress>) {
+    let (tx, rx) = pipe(corrupt);
+    let receiver = thread::spawn(move || reference.receive(rx));
+
+    let mut events = vec![];
+    let sent = send("image.bin", Cursor::new(data.to_vec()), tx, |p| {
+        events.push(p)
+    });
+
+    (sent, receiver.join().expect("receiver panicked"), events)
+}
+
+fn image(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+}
+
+#[test]
+fn test_crc() {
+    let crc16 = Crc::new(false).update(b"123456789").bytes();
+    assert_eq!(crc16, vec![0x31, 0xC3]);
+
+    let crc32 = Crc::new(true).update(b"1234").update(b"56789").bytes();
+    assert_eq!(crc32, vec![0x26, 0x39, 0xF4, 0xCB]);
+}
+
+#[test]
+fn test_header_round_trip() {
+    let (a, b) = pipe(None);
+    let (mut a, mut b) = (Link::new(a), Link::new(b));
+    let position = Header::with_position(ZRPOS, 0x1811_9013);
+    let flags = Header::new(ZRINIT, [0, 4, 0, CANFC32 | CANFDX]);
+
+    a.write_hex_header(&flags).expect("hex header");
+    a.write_bin_header(&position).expect("bin header");
+    a.crc32 = true;
+    a.write_bin_header(&position).expect("bin32 header");
+
+    assert_eq!(b.read_header().expect("hex header"), flags);
+    assert_eq!(b.read_header().expect("bin header"), position);
+    assert_eq!(b.read_header().expect("bin32 header"), position);
+    assert_eq!(position.position(), 0x1811_9013);
+    assert_eq!(flags.flags(), CANFC32 | CANFDX);
+}
+
+#[test]
+fn test_escaped_subpacket() {
+    let (a, b) = pipe(None);
+    let (mut a, mut b) = (Link::new(a), Link::new(b));
+    let data: Vec<u8> = (0..256).map(|b| b as u8).collect();
+
+    a.write_subpacket(&data, ZCRCW).expect("subpacket");
+    assert_eq!(read_subpacket(&mut b).expect("subpacket"), (data, ZCRCW));
+}
+
+#[test]
+fn test_known_hex_headers() {
+    let mut link = Link::new(Cursor::new(vec![]));
+    link.write_hex_header(&Header::new(ZRQINIT, [0; 4])).expect("ZRQINIT");
+    let zrqinit = b"**\x18B00000000000000\r\x8a\x11";
+    assert_eq!(&link.inner.get_ref()[..], &zrqinit[..]);
+
+    // As sent by lrzsz's `rz`: full duplex, overlapped I/O and CRC-32.
+    let zrinit = b"**\x18B0100000023be50\r\x8a\x11";
+    let mut link = Link::new(Cursor::new(zrinit.to_vec()));
+    let flags = CANFDX | CANOVIO | CANFC32;
+    let expected = Header::new(ZRINIT, [0, 0, 0, flags]);
+    assert_eq!(link.read_header().expect("ZRINIT"), expected);
+}
+
+#[test]
+fn test_known_bin32_header() {
+    let zfile = [
+        ZPAD, ZDLE, ZBIN32, ZFILE, 0, 0, 0, ZCBIN, 0x4B, 0x61, 0xA5, 0x44,
+    ];
+    let header = Header::new(ZFILE, [0, 0, 0, ZCBIN]);
+
+    let mut link = Link::new(Cursor::new(vec![]));
+    link.crc32 = true;
+    link.write_bin_header(&header).expect("ZFILE");
+    assert_eq!(&link.inner.get_ref()[..], &zfile[..]);
+
+    let mut link = Link::new(Cursor::new(zfile.to_vec()));
+    assert_eq!(link.read_header().expect("ZFILE"), header);
+}
+
+#[test]
+fn test_known_subpackets() {
+    let data = [ZDLE, XON, b'A', 0x93];
+    let crc32 = [
+        ZDLE, 0x58, ZDLE, 0x51, 0x41, ZDLE, 0xD3, ZDLE, ZCRCE, 0xE0, 0xCC,
+        0x4A, 0x16,
+    ];
+    let crc16 = [
+        ZDLE, 0x58, ZDLE, 0x51, 0x41, ZDLE, 0xD3, ZDLE, ZCRCW, 0xD1, 0x8C,
+        XON,
+    ];
+
+    let cases = [(true, ZCRCE, &crc32[..]), (false, ZCRCW, &crc16[..])];
+    for &(crc32, end, expected) in &cases {
+        let mut link = Link::new(Cursor::new(vec![]));
+        link.crc32 = crc32;
+        link.write_subpacket(&data, end).expect("subpacket");
+        assert_eq!(&link.inner.get_ref()[..], expected);
+
+        let mut link = Link::new(Cursor::new(expected.to_vec()));
+        link.crc32 = crc32;
+        let subpacket = read_subpacket(&mut link).expect("subpacket");
+        assert_eq!(subpacket, (data.to_vec(), end));
+    }
+}
+
+#[test]
+fn test_streaming_crc32() {
+    let data = image(40_000);
+    let flags = CANFDX | CANOVIO | CANFC32;
+    let (sent, received, events) = transfer(&data, Reference::new(flags), None);
+
+    assert_eq!(sent.expect("sent"), data.len());
+    let (name, file) = received.expect("received");
+    assert_eq!(name, "image.bin");
+    assert_eq!(file, data);
+
+    assert_eq!(events[0], Progress::Waiting);
+    assert_eq!(events[1], Progress::Started);
+    match events[events.len() - 1] {
+        Progress::Terminated { stats } => {
+            assert_eq!(stats.bytes, data.len());
+            assert_eq!(stats.total, Some(data.len()));
+            assert_eq!(stats.packets, 40);
+            assert_eq!(stats.retries, 0);
+        }
+        ref e => panic!("unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn test_streaming_without_zcrcq_acks() {
+    let data = image(40_000);
+    let reference = Reference {
+        quiet: true,
+        ..Reference::new(CANFDX | CANOVIO | CANFC32)
+    };
+
+    let (sent, received, events) = transfer(&data, reference, None);
+    assert_eq!(sent.expect("sent"), data.len());
+    assert_eq!(received.expect("received").1, data);
+    match events[events.len() - 1] {
+        Progress::Terminated { stats } => assert_eq!(stats.retries, 0),
+        ref e => panic!("unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn test_windowed_crc16() {
+    let data = image(5_000);
+    let reference = Reference {
+        buffer: 2048,
+        ..Reference::new(CANFDX)
+    };
+
+    let (sent, received, _) = transfer(&data, reference, None);
+    assert_eq!(sent.expect("sent"), data.len());
+    assert_eq!(received.expect("received").1, data);
+}
+
+#[test]
+fn test_resume_from_offset() {
+    let data = image(10_000);
+    let reference = Reference {
+        file: data[..3_000].to_vec(),
+        ..Reference::new(CANFDX | CANOVIO | CANFC32)
+    };
+
+    let (sent, received, events) = transfer(&data, reference, None);
+    assert_eq!(sent.expect("sent"), 7_000);
+    assert_eq!(received.expect("received").1, data);
+
+    let packets = events.iter().filter(|e| match **e {
+        Progress::Packet { .. } => true,
+        _ => false,
+    });
+    assert_eq!(packets.count(), 7);
+}
+
+#[test]
+fn test_corruption_resends() {
+    let data = vec![b'a'; 40_000];
+    let flags = CANFDX | CANOVIO | CANFC32;
+    let (sent, received, events) =
+        transfer(&data, Reference::new(flags), Some(20_000));
+
+    assert_eq!(sent.expect("sent"), data.len());
+    assert_eq!(received.expect("received").1, data);
+    match events[events.len() - 1] {
+        Progress::Terminated { stats } => assert!(stats.retries > 0),
+        ref e => panic!("unexpected event {:?}", e),
+    }
+}
+
+#[test]
+fn test_receiver_cancels() {
+    let reference = Reference {
+        cancel: true,
+        ..Reference::new(CANFDX | CANOVIO | CANFC32)
+    };
+
+    let (sent, received, events) = transfer(&image(1_000), reference, None);
+    let e = sent.expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert!(received.is_err());
+
+    match events[events.len() - 1] {
+        Progress::Cancelled { reason, .. } => {
+            assert_eq!(reason, CancelReason::Received)
+        }
+        ref e => panic!("unexpected event {:?}", e),
+    }
+}
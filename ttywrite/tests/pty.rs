@@ -0,0 +1,176 @@
+//! Uploads images with `ttywrite` to the simulated Pi, `pisim`, over a
+//! pseudo-terminal.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the path of the binary called `name` built alongside this test.
+fn binary(name: &str) -> PathBuf {
+    let mut dir = env::current_exe().expect("test binary path");
+    dir.pop();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+
+    dir.join(name)
+}
+
+/// A scratch directory, removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Scratch {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let unique = format!("ttywrite-{}-{}", name, now.subsec_nanos());
+        let dir = env::temp_dir().join(unique);
+        fs::create_dir_all(&dir).expect("scratch dir");
+        Scratch(dir)
+    }
+
+    fn file(&self, name: &str, contents: &[u8]) -> PathBuf {
+        let path = self.0.join(name);
+        File::create(&path)
+            .and_then(|mut file| file.write_all(contents))
+            .expect("write");
+        path
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A running `pisim`, killed when dropped.
+struct Pisim {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    tty:   PathBuf,
+}
+
+impl Pisim {
+    /// Starts `pisim`, writing images to `output`.
+    fn start(output: &Path) -> Pisim {
+        let mut child = Command::new(binary("pisim"))
+            .arg("--output")
+            .arg(output)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("pisim started");
+
+        let stdout = child.stdout.take().unwrap();
+        let mut lines = BufReader::new(stdout).lines();
+        let first = lines.next().expect("pisim output").expect("line");
+        let tty = first.trim_left_matches("pisim: listening on ").into();
+        Pisim { child, lines, tty }
+    }
+
+    fn next_line(&mut self) -> String {
+        self.lines.next().expect("pisim output").expect("line")
+    }
+}
+
+impl Drop for Pisim {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Runs `command`, a `ttywrite` invocation, with stdin closed.
+fn ttywrite(command: &mut Command) -> Output {
+    command.stdin(Stdio::null()).output().expect("ttywrite ran")
+}
+
+fn image(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 13 + 5) as u8).collect()
+}
+
+fn received(dir: &Path, name: &str) -> Vec<u8> {
+    let mut data = vec![];
+    File::open(dir.join(name))
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .expect("received image");
+    data
+}
+
+#[test]
+fn test_upload_applies_settings() {
+    let scratch = Scratch::new("pty-upload");
+    let data = image(1_000);
+    let input = scratch.file("kernel.bin", &data);
+    let mut pisim = Pisim::start(&scratch.0);
+
+    let output = ttywrite(
+        Command::new(binary("ttywrite"))
+            .arg("-i")
+            .arg(&input)
+            .args(&["-b", "57600", "-s", "2", "-f", "software"])
+            .arg(&pisim.tty),
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("wrote 1000 bytes to input"), "{}", stdout);
+
+    assert_eq!(
+        pisim.next_line(),
+        "pisim: received image-1.bin (1024 bytes) at 57600 baud, 8N2, flow \
+         control software"
+    );
+
+    let image = received(&scratch.0, "image-1.bin");
+    assert_eq!(&image[..data.len()], &data[..]);
+    assert!(image[data.len()..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn test_profile_and_json() {
+    let scratch = Scratch::new("pty-profile");
+    let data = image(300);
+    let input = scratch.file("kernel.bin", &data);
+    let mut pisim = Pisim::start(&scratch.0);
+
+    let config = format!(
+        "[sim]\ndevice = {}\nbaud = 9600\nprotocol = xmodem\n",
+        pisim.tty.display()
+    );
+    let config = scratch.file("profiles", config.as_bytes());
+
+    let output = ttywrite(
+        Command::new(binary("ttywrite"))
+            .arg("--config")
+            .arg(&config)
+            .args(&["--profile", "sim", "--json", "-i"])
+            .arg(&input),
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let json = String::from_utf8_lossy(&output.stdout);
+    assert!(json.starts_with("{\"status\":\"ok\",\"exit_code\":0,\
+                              \"bytes\":300,\"packets\":3,\"retries\":"),
+            "{}", json);
+
+    assert_eq!(
+        pisim.next_line(),
+        "pisim: received image-1.bin (384 bytes) at 9600 baud, 8N1, flow \
+         control none"
+    );
+    assert_eq!(&received(&scratch.0, "image-1.bin")[..300], &data[..]);
+}
+
+#[test]
+fn test_watch_without_input() {
+    let output = ttywrite(
+        Command::new(binary("ttywrite"))
+            .args(&["--watch", "--json", "/dev/null"]),
+    );
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let json = String::from_utf8_lossy(&output.stdout);
+    assert!(json.starts_with("{\"status\":\"error\",\"exit_code\":1,\
+                              \"error\":\"usage\","),
+            "{}", json);
+}
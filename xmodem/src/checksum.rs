@@ -0,0 +1,47 @@
+/// The error detection scheme used to verify the data of each packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// The original 8-bit additive checksum. Receivers request it with `NAK`.
+    Standard,
+    /// A 16-bit CRC-16/CCITT (XMODEM-CRC). Receivers request it with `C`.
+    Crc16,
+}
+
+impl Checksum {
+    /// Returns the number of bytes this checksum occupies on the wire.
+    pub fn size(&self) -> usize {
+        match *self {
+            Checksum::Standard => 1,
+            Checksum::Crc16 => 2,
+        }
+    }
+
+    /// Computes this checksum over `buf`, writing its wire representation into
+    /// the first `self.size()` bytes of `out`.
+    pub(crate) fn compute(&self, buf: &[u8], out: &mut [u8]) {
+        match *self {
+            Checksum::Standard => out[0] = additive(buf),
+            Checksum::Crc16 => {
+                let crc = crc16(buf);
+                out[0] = (crc >> 8) as u8;
+                out[1] = crc as u8;
+            }
+        }
+    }
+}
+
+/// Computes the 8-bit additive checksum of `buf`.
+pub(crate) fn additive(buf: &[u8]) -> u8 {
+    buf.iter().fold(0u8, |a, &b| a.wrapping_add(b))
+}
+
+/// Computes the CRC-16/CCITT of `buf` as used by XMODEM-CRC: polynomial
+/// `0x1021` with an initial value of zero.
+pub(crate) fn crc16(buf: &[u8]) -> u16 {
+    buf.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x1021,
+        })
+    })
+}
@@ -0,0 +1,29 @@
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Type for clock functions. A clock returns the current time in
+/// microseconds; only the difference between two readings is ever used.
+pub type ClockFn = fn() -> u64;
+
+/// The clock used by default: the system time with the `std` feature, the
+/// Raspberry Pi's system timer with only the `pi` feature, and otherwise a
+/// clock that never advances.
+#[cfg(feature = "std")]
+pub(crate) const DEFAULT: ClockFn = system;
+#[cfg(all(not(feature = "std"), feature = "pi"))]
+pub(crate) const DEFAULT: ClockFn = ::pi::timer::current_time;
+#[cfg(all(not(feature = "std"), not(feature = "pi")))]
+pub(crate) const DEFAULT: ClockFn = stopped;
+
+/// Clock reading the system time.
+#[cfg(feature = "std")]
+pub fn system() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1_000_000 + d.subsec_nanos() as u64 / 1_000)
+        .unwrap_or(0)
+}
+
+/// Clock that never advances. Timeouts measured by this clock never elapse.
+#[cfg(all(not(feature = "std"), not(feature = "pi")))]
+pub fn stopped() -> u64 { 0 }
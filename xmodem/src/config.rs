@@ -0,0 +1,80 @@
+use checksum::Checksum;
+use clock::{self, ClockFn};
+
+/// Configuration for an XMODEM session.
+///
+/// A value of this type is passed to [`Xmodem::new_with_progress()`],
+/// [`Xmodem::new_with_config()`] and the `*_with_config` transfer functions.
+/// The transmitter always uses whichever checksum the receiver requests, so
+/// only the receiving side consults `checksum` and `crc_probes`.
+///
+/// All times are in microseconds as measured by `clock`.
+#[derive(Debug, Copy, Clone)]
+pub struct XmodemConfig {
+    /// The checksum the receiver requests when reception starts.
+    pub checksum:         Checksum,
+    /// The number of unanswered `C` probes a receiver sends before falling
+    /// back to `Checksum::Standard`. A probe is unanswered when reading the
+    /// response times out.
+    pub crc_probes:       usize,
+    /// The number of times a packet is sent or requested before the session
+    /// is aborted. This also bounds the number of `NAK` or `C` probes a
+    /// receiver sends when starting.
+    pub max_retries:      usize,
+    /// How long a transmitter waits for the receiver's initial `NAK` or `C`.
+    /// If `None`, only the timeout of the inner reader (if any) applies.
+    pub initial_nak_wait: Option<u64>,
+    /// How long to wait for any other single byte. If `None`, only the
+    /// timeout of the inner reader (if any) applies.
+    pub byte_timeout:     Option<u64>,
+    /// The clock used to measure `initial_nak_wait` and `byte_timeout`.
+    pub clock:            ClockFn,
+    /// Whether to send two `CAN` bytes instead of one when aborting, so that
+    /// a single corrupted byte cannot be mistaken for a cancellation.
+    pub double_can:       bool,
+    /// The number of data bytes the transfer carries, if known in advance.
+    /// It is reported to progress callbacks as `Stats::total` and used by
+    /// receivers to strip the padding unless `trim` is `Trim::Keep`.
+    pub length_hint:      Option<usize>,
+    /// The byte a transmitter pads the final packet with. Use [`SUB`] for
+    /// the CP/M convention.
+    pub padding:          u8,
+    /// How a receiver strips the padding of the final packet. Defaults to
+    /// `Trim::Keep`, so no data is ever lost.
+    pub trim:             Trim,
+}
+
+/// How a receiver strips the padding of the final packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trim {
+    /// Every byte received is kept, so the received data is a multiple of 128
+    /// bytes long.
+    Keep,
+    /// Data beyond `length_hint` bytes is discarded if the length is known.
+    /// Otherwise, trailing `padding` bytes of the final packet are discarded:
+    /// data that itself ends with `padding` bytes loses them, so `Trim::Keep`
+    /// should be used for data that may end with the padding byte when the
+    /// length is unknown.
+    Padding,
+    /// Data beyond `length_hint` bytes is discarded. Every byte is kept if
+    /// `length_hint` is `None`.
+    Length,
+}
+
+impl Default for XmodemConfig {
+    fn default() -> XmodemConfig {
+        XmodemConfig {
+            checksum:         Checksum::Standard,
+            crc_probes:       3,
+            max_retries:      10,
+            initial_nak_wait: None,
+            byte_timeout:     None,
+            clock:            clock::DEFAULT,
+            double_can:       false,
+            length_hint:      None,
+            padding:          0,
+            trim:             Trim::Keep,
+        }
+    }
+}
+
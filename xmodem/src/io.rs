@@ -0,0 +1,260 @@
+//! Byte-oriented I/O used by the protocol implementation.
+//!
+//! With the `std` feature enabled (the default), `Error`, `ErrorKind` and
+//! `Result` are those of `std::io`, and every `std::io::Read` and
+//! `std::io::Write` type implements [`ByteRead`] and [`ByteWrite`]. Without
+//! it, the crate is `no_std` and provides its own minimal error type with the
+//! same interface.
+//!
+//! With the `pi` feature enabled and `std` disabled, `pi::uart::UART`
+//! implements both traits directly. With both features enabled it does so
+//! through its `std::io` implementations.
+
+#[cfg(feature = "std")]
+use std::io as std_io;
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Result};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::{Error, ErrorKind, Result};
+
+/// A source of bytes.
+pub trait ByteRead {
+    /// Reads a single byte. Returns `None` if the end of the input has been
+    /// reached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `TimedOut` or `WouldBlock` if no byte is
+    /// available yet, and an error of kind `Interrupted` if the read should
+    /// simply be attempted again.
+    fn read_byte(&mut self) -> Result<Option<u8>>;
+
+    /// Reads bytes into `buf` until it is full or the end of the input has
+    /// been reached. Returns the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from `read_byte` other than `Interrupted`.
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.read_byte() {
+                Ok(Some(byte)) => {
+                    buf[read] = byte;
+                    read += 1;
+                }
+                Ok(None) => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+/// A sink for bytes.
+pub trait ByteWrite {
+    /// Writes a single byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the byte could not be written.
+    fn write_byte(&mut self, byte: u8) -> Result<()>;
+
+    /// Writes all of `buf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from `write_byte`.
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        for byte in buf {
+            self.write_byte(*byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensures that all buffered bytes reach their destination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not all bytes could be written.
+    fn flush(&mut self) -> Result<()> { Ok(()) }
+}
+
+#[cfg(feature = "std")]
+impl<T: std_io::Read> ByteRead for T {
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut buf = [0u8; 1];
+        match std_io::Read::read(self, &mut buf)? {
+            0 => Ok(None),
+            _ => Ok(Some(buf[0])),
+        }
+    }
+
+    fn read_bytes(&mut self, mut buf: &mut [u8]) -> Result<usize> {
+        let start_len = buf.len();
+        while !buf.is_empty() {
+            match std_io::Read::read(self, buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(start_len - buf.len())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: std_io::Write> ByteWrite for T {
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        std_io::Write::write_all(self, &[byte])
+    }
+
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        std_io::Write::write_all(self, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> { std_io::Write::flush(self) }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'a, T: ByteRead + ?Sized> ByteRead for &'a mut T {
+    fn read_byte(&mut self) -> Result<Option<u8>> { (**self).read_byte() }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read_bytes(buf)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'a, T: ByteWrite + ?Sized> ByteWrite for &'a mut T {
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        (**self).write_byte(byte)
+    }
+
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_bytes(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> { (**self).flush() }
+}
+
+/// Reading from a slice consumes its bytes.
+#[cfg(not(feature = "std"))]
+impl<'a> ByteRead for &'a [u8] {
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        match self.split_first() {
+            Some((byte, rest)) => {
+                *self = rest;
+                Ok(Some(*byte))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Writing to a slice fills it from the front. Writing to a full slice fails
+/// with an error of kind `WriteZero`.
+#[cfg(not(feature = "std"))]
+impl<'a> ByteWrite for &'a mut [u8] {
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        let slice = ::core::mem::replace(self, &mut []);
+        match slice.split_first_mut() {
+            Some((first, rest)) => {
+                *first = byte;
+                *self = rest;
+                Ok(())
+            }
+            None => Err(Error::new(
+                ErrorKind::WriteZero,
+                "failed to write whole buffer",
+            )),
+        }
+    }
+}
+
+#[cfg(all(feature = "pi", not(feature = "std")))]
+impl ByteRead for ::pi::uart::UART {
+    /// Waits for a byte for at most the UART's read timeout, if one is set.
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        match self.wait_for_byte() {
+            Ok(()) => Ok(Some(::pi::uart::UART::read_byte(self))),
+            Err(()) => Err(Error::new(ErrorKind::TimedOut, "Timeout")),
+        }
+    }
+}
+
+#[cfg(all(feature = "pi", not(feature = "std")))]
+impl ByteWrite for ::pi::uart::UART {
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        ::pi::uart::UART::write_byte(self, byte);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use core::fmt;
+    use core::result;
+
+    /// A specialized `Result` type for I/O operations.
+    pub type Result<T> = result::Result<T, Error>;
+
+    /// A list specifying general categories of I/O error, mirroring
+    /// `std::io::ErrorKind`.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum ErrorKind {
+        /// The connection was aborted by the other side.
+        ConnectionAborted,
+        /// The operation failed because a pipe was closed.
+        BrokenPipe,
+        /// The operation needs to block to complete.
+        WouldBlock,
+        /// A parameter was incorrect.
+        InvalidInput,
+        /// Data not valid for the operation were encountered.
+        InvalidData,
+        /// The I/O operation's timeout expired.
+        TimedOut,
+        /// A write returned without writing any bytes.
+        WriteZero,
+        /// The operation was interrupted and can be retried.
+        Interrupted,
+        /// Any I/O error not part of this list.
+        Other,
+        /// The end of the input was reached prematurely.
+        UnexpectedEof,
+    }
+
+    /// The error type for I/O operations, mirroring `std::io::Error`.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct Error {
+        kind: ErrorKind,
+        msg:  &'static str,
+    }
+
+    impl Error {
+        /// Returns a new error of kind `kind` described by `msg`.
+        pub fn new(kind: ErrorKind, msg: &'static str) -> Error {
+            Error { kind, msg }
+        }
+
+        /// Returns the corresponding `ErrorKind` for this error.
+        pub fn kind(&self) -> ErrorKind { self.kind }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.msg)
+        }
+    }
+}
@@ -1,27 +1,74 @@
-use std::io;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[cfg(test)]
-mod tests;
-mod read_ext;
-mod progress;
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "pi")]
+extern crate pi;
 
-pub use progress::{Progress, ProgressFn};
+use core::cmp;
 
-use read_ext::ReadExt;
+#[cfg(all(test, feature = "std"))]
+mod tests;
+mod progress;
+mod checksum;
+mod clock;
+mod config;
+mod machine;
+#[cfg(feature = "std")]
+mod ymodem;
+pub mod io;
+
+pub use checksum::Checksum;
+pub use clock::ClockFn;
+pub use config::{Trim, XmodemConfig};
+pub use machine::{Event, Receiver, Transmitter};
+pub use progress::{CancelReason, Progress, ProgressFn, RetryReason, Stats};
+#[cfg(feature = "std")]
+pub use ymodem::{FileInfo, Ymodem};
+
+use io::{ByteRead, ByteWrite};
 
 const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
 const EOT: u8 = 0x04;
 const ACK: u8 = 0x06;
 const NAK: u8 = 0x15;
 const CAN: u8 = 0x18;
+const CRC: u8 = b'C';
+
+/// The CP/M end of file marker (`^Z`), conventionally used to pad the final
+/// packet of text files.
+pub const SUB: u8 = 0x1A;
 const BUF_LEN: usize = 128;
+const BUF_LEN_1K: usize = 1024;
+
+/// The number of times in a row the receiver may reject the first 1024 byte
+/// block before the transmitter assumes it does not support XMODEM-1K.
+const MAX_1K_REJECTIONS: usize = 3;
+
+/// Implementation of the XMODEM protocol over blocking I/O.
+///
+/// The protocol itself is implemented by the [`Receiver`] and [`Transmitter`]
+/// state machines; an `Xmodem` feeds them bytes read from the inner stream
+/// and writes out whatever they produce.
+///
+/// The `P` generic is the type of the progress callback: any `FnMut(Progress)`
+/// closure or a plain [`ProgressFn`].
+pub struct Xmodem<R, P = ProgressFn> {
+    inner:       R,
+    receiver:    Receiver,
+    transmitter: Transmitter,
+    config:      XmodemConfig,
+    started_at:  Option<u64>,
+    stats:       Stats,
+    progress:    P,
+}
 
-/// Implementation of the XMODEM protocol.
-pub struct Xmodem<R> {
-    packet:   u8,
-    inner:    R,
-    started:  bool,
-    progress: ProgressFn,
+/// The state machine driven by a call.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Role {
+    Receive,
+    Transmit,
 }
 
 impl Xmodem<()> {
@@ -29,12 +76,12 @@ impl Xmodem<()> {
     /// length of the total data yielded by `data` is not a multiple of 128
     /// bytes, the data is padded with zeroes and sent to the receiver.
     ///
-    /// Returns the number of bytes written to `to`, excluding padding zeroes.
+    /// Returns the number of bytes written to `to`, excluding padding.
     #[inline]
     pub fn transmit<R, W>(data: R, to: W) -> io::Result<usize>
     where
-        W: io::Read + io::Write,
-        R: io::Read,
+        W: ByteRead + ByteWrite,
+        R: ByteRead,
     {
         Xmodem::transmit_with_progress(data, to, progress::noop)
     }
@@ -43,222 +90,340 @@ impl Xmodem<()> {
     /// length of the total data yielded by `data` is not a multiple of 128
     /// bytes, the data is padded with zeroes and sent to the receiver.
     ///
+    /// Data is sent in 1024 byte (XMODEM-1K) blocks while at least 1024 bytes
+    /// remain. The tail of the data is sent in 128 byte blocks. Rejected blocks
+    /// are sent again at the same size, except that if the receiver rejects the
+    /// first 1024 byte block three times in a row, the transmitter falls back
+    /// to 128 byte blocks for the remainder of the transmission.
+    ///
     /// The function `f` is used as a callback to indicate progress throughout
     /// the transmission. See the [`Progress`] enum for more information.
     ///
-    /// Returns the number of bytes written to `to`, excluding padding zeroes.
-    pub fn transmit_with_progress<R, W>(
-        mut data: R,
+    /// Returns the number of bytes written to `to`, excluding padding.
+    #[inline]
+    pub fn transmit_with_progress<R, W, F>(
+        data: R,
         to: W,
-        f: ProgressFn,
+        f: F,
     ) -> io::Result<usize>
     where
-        W: io::Read + io::Write,
-        R: io::Read,
+        W: ByteRead + ByteWrite,
+        R: ByteRead,
+        F: FnMut(Progress),
     {
-        let mut transmitter = Xmodem::new_with_progress(to, f);
-        let mut packet = [0u8; 128];
-        let mut written = 0;
-        'next_packet: loop {
-            let n = data.read_max(&mut packet)?;
-            packet[n..].iter_mut().for_each(|b| *b = 0);
-
-            if n == 0 {
-                transmitter.write_packet(&[])?;
-                return Ok(written);
-            }
-
-            for _ in 0..10 {
-                match transmitter.write_packet(&packet) {
-                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
-                        continue
-                    }
-                    Err(e) => return Err(e),
-                    Ok(_) => {
-                        written += n;
-                        continue 'next_packet;
-                    }
-                }
-            }
+        Xmodem::transmit_with_config(data, to, XmodemConfig::default(), f)
+    }
 
-            return Err(io::Error::new(
-                io::ErrorKind::BrokenPipe,
-                "bad transmit",
-            ));
-        }
+    /// Transmits `data` to the receiver `to` using the XMODEM protocol as
+    /// described in [`Xmodem::transmit_with_progress()`].
+    ///
+    /// The final packet is padded with `config.padding`. Retries, timeouts and
+    /// cancellation are governed by `config`. See [`XmodemConfig`] for more
+    /// information. The function `f` is used as a callback to indicate
+    /// progress throughout the transmission. See the [`Progress`] enum for
+    /// more information.
+    ///
+    /// Returns the number of bytes written to `to`, excluding padding.
+    pub fn transmit_with_config<R, W, F>(
+        data: R,
+        to: W,
+        config: XmodemConfig,
+        f: F,
+    ) -> io::Result<usize>
+    where
+        W: ByteRead + ByteWrite,
+        R: ByteRead,
+        F: FnMut(Progress),
+    {
+        Xmodem::new_with_progress(to, config, f).send_data(data, None)
     }
 
     /// Receives `data` from `from` using the XMODEM protocol and writes it into
-    /// `into`. Returns the number of bytes read from `from`, a multiple of 128.
+    /// `into`. Returns the number of bytes read from `from`, a multiple of 128:
+    /// the padding of the final packet is kept.
     #[inline]
     pub fn receive<R, W>(from: R, into: W) -> io::Result<usize>
     where
-        R: io::Read + io::Write,
-        W: io::Write,
+        R: ByteRead + ByteWrite,
+        W: ByteWrite,
     {
         Xmodem::receive_with_progress(from, into, progress::noop)
     }
 
     /// Receives `data` from `from` using the XMODEM protocol and writes it into
-    /// `into`. Returns the number of bytes read from `from`, a multiple of 128.
+    /// `into`. Returns the number of bytes read from `from`, a multiple of 128:
+    /// the padding of the final packet is kept.
     ///
     /// The function `f` is used as a callback to indicate progress throughout
     /// the reception. See the [`Progress`] enum for more information.
-    pub fn receive_with_progress<R, W>(
+    #[inline]
+    pub fn receive_with_progress<R, W, F>(
         from: R,
-        mut into: W,
-        f: ProgressFn,
+        into: W,
+        f: F,
     ) -> io::Result<usize>
     where
-        R: io::Read + io::Write,
-        W: io::Write,
+        R: ByteRead + ByteWrite,
+        W: ByteWrite,
+        F: FnMut(Progress),
     {
-        let mut receiver = Xmodem::new_with_progress(from, f);
-        let mut packet = [0u8; 128];
-        let mut received = 0;
-        'next_packet: loop {
-            for _ in 0..10 {
-                match receiver.read_packet(&mut packet) {
-                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
-                        continue
-                    }
-                    Err(e) => return Err(e),
-                    Ok(0) => break 'next_packet,
-                    Ok(n) => {
-                        received += n;
-                        into.write_all(&packet)?;
-                        continue 'next_packet;
-                    }
-                }
-            }
+        Xmodem::receive_with_config(from, into, XmodemConfig::default(), f)
+    }
 
-            return Err(io::Error::new(
-                io::ErrorKind::BrokenPipe,
-                "bad receive",
-            ));
-        }
+    /// Receives `data` from `from` using the XMODEM protocol and writes it into
+    /// `into`. Returns the number of bytes written to `into`, which is a
+    /// multiple of 128 if `config.trim` is `Trim::Keep`.
+    ///
+    /// The receiver requests the checksum given by `config`, which also
+    /// governs retries, timeouts and cancellation. See [`XmodemConfig`] and
+    /// [`Trim`] for more information. The function `f` is used as a callback
+    /// to indicate progress throughout the reception. See the [`Progress`]
+    /// enum for more information.
+    pub fn receive_with_config<R, W, F>(
+        from: R,
+        into: W,
+        config: XmodemConfig,
+        f: F,
+    ) -> io::Result<usize>
+    where
+        R: ByteRead + ByteWrite,
+        W: ByteWrite,
+        F: FnMut(Progress),
+    {
+        let limit = match config.trim {
+            Trim::Keep => None,
+            _ => config.length_hint,
+        };
 
-        Ok(received)
+        Xmodem::new_with_progress(from, config, f).receive_data(into, limit)
     }
 }
 
-impl<T: io::Read + io::Write> Xmodem<T> {
+impl<T: ByteRead + ByteWrite> Xmodem<T> {
     /// Returns a new `Xmodem` instance with the internal reader/writer set to
     /// `inner`. The returned instance can be used for both receiving
     /// (downloading) and sending (uploading).
     pub fn new(inner: T) -> Self {
-        Xmodem {
-            packet: 1,
-            started: false,
-            inner,
-            progress: progress::noop,
-        }
+        Xmodem::new_with_config(inner, XmodemConfig::default())
     }
 
     /// Returns a new `Xmodem` instance with the internal reader/writer set to
-    /// `inner`. The returned instance can be used for both receiving
-    /// (downloading) and sending (uploading). The function `f` is used as a
-    /// callback to indicate progress throughout the transfer. See the
-    /// [`Progress`] enum for more information.
-    pub fn new_with_progress(inner: T, f: ProgressFn) -> Self {
+    /// `inner` using the session configuration `config`. The returned instance
+    /// can be used for both receiving (downloading) and sending (uploading).
+    /// See [`XmodemConfig`] for more information.
+    pub fn new_with_config(inner: T, config: XmodemConfig) -> Self {
+        Xmodem::new_with_progress(inner, config, progress::noop)
+    }
+}
+
+impl<T: ByteRead + ByteWrite, P: FnMut(Progress)> Xmodem<T, P> {
+    /// Returns a new `Xmodem` instance with the internal reader/writer set to
+    /// `inner` using the session configuration `config`. The returned instance
+    /// can be used for both receiving (downloading) and sending (uploading).
+    /// The function `f` is used as a callback to indicate progress throughout
+    /// the transfer. See the [`XmodemConfig`] struct and [`Progress`] enum for
+    /// more information.
+    pub fn new_with_progress(inner: T, config: XmodemConfig, f: P) -> Self {
         Xmodem {
-            packet: 1,
-            started: false,
             inner,
+            receiver: Receiver::new(config),
+            transmitter: Transmitter::new(config),
+            config,
+            started_at: None,
+            stats: Stats {
+                total: config.length_hint,
+                ..Stats::default()
+            },
             progress: f,
         }
     }
 
-    /// Reads a single byte from the inner I/O stream. If `abort_on_can` is
-    /// `true`, an error of `ConnectionAborted` is returned if the read byte is
-    /// `CAN`.
+    /// Reads a single byte from the inner I/O stream, waiting at most
+    /// `timeout` microseconds as measured by `config.clock`.
+    ///
+    /// If `timeout` is `Some`, reads failing with `TimedOut` or `WouldBlock`
+    /// are retried until the timeout elapses. Otherwise, those errors are
+    /// returned as is.
     ///
     /// # Errors
     ///
-    /// Returns an error if reading from the inner stream fails or if
-    /// `abort_on_can` is `true` and the read byte is `CAN`.
-    fn read_byte(&mut self, abort_on_can: bool) -> io::Result<u8> {
-        let mut buf = [0u8; 1];
-        self.inner.read_exact(&mut buf)?;
+    /// Returns an error if reading from the inner stream fails. An error of
+    /// kind `TimedOut` is returned if no byte arrives in time.
+    fn read_byte(&mut self, timeout: Option<u64>) -> io::Result<u8> {
+        let start = (self.config.clock)();
+        loop {
+            match self.inner.read_byte() {
+                Ok(None) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ))
+                }
+                Ok(Some(byte)) => return Ok(byte),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if timeout.is_some() && is_timeout(e) => {}
+                Err(e) => return Err(e),
+            }
 
-        let byte = buf[0];
-        if abort_on_can && byte == CAN {
-            return Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "received CAN",
-            ));
+            match timeout {
+                Some(t) if (self.config.clock)().wrapping_sub(start) >= t => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out waiting for a byte",
+                    ))
+                }
+                _ => {}
+            }
         }
-
-        Ok(byte)
     }
 
-    /// Writes a single byte to the inner I/O stream.
+    /// Drives the state machine for `role` until `f` returns a result for one
+    /// of its events: its pending output is written to the inner stream, its
+    /// events are reported to the progress callback and passed to `f`, and
+    /// bytes read from the inner stream are fed to it. A read that times out
+    /// is reported to the machine instead.
     ///
     /// # Errors
     ///
-    /// Returns an error if writing to the inner stream fails.
-    fn write_byte(&mut self, byte: u8) -> io::Result<()> {
-        self.inner.write_all(&[byte])
+    /// Returns any error returned by `f` or by reading or writing the inner
+    /// stream. An error of kind `InvalidInput` is returned if the machine's
+    /// session is over before `f` returns.
+    fn drive<F>(&mut self, role: Role, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut(&Self, Event) -> Option<io::Result<usize>>,
+    {
+        loop {
+            let sent = {
+                let pending = match role {
+                    Role::Receive => self.receiver.poll_transmit(),
+                    Role::Transmit => self.transmitter.poll_transmit(),
+                };
+                self.inner.write_bytes(pending)?;
+                pending.len()
+            };
+
+            match role {
+                Role::Receive => self.receiver.transmitted(sent),
+                Role::Transmit => self.transmitter.transmitted(sent),
+            }
+
+            loop {
+                let event = match role {
+                    Role::Receive => self.receiver.poll_event(),
+                    Role::Transmit => self.transmitter.poll_event(),
+                };
+
+                match event {
+                    Some(event) => {
+                        self.report(event);
+                        if let Some(result) = f(self, event) {
+                            return result;
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            let (finished, timeout) = match role {
+                Role::Receive => {
+                    (self.receiver.is_finished(), self.receiver.timeout())
+                }
+                Role::Transmit => {
+                    (self.transmitter.is_finished(), self.transmitter.timeout())
+                }
+            };
+
+            if finished {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the session is over",
+                ));
+            }
+
+            match (self.read_byte(timeout), role) {
+                (Ok(byte), Role::Receive) => self.receiver.receive_byte(byte)?,
+                (Ok(byte), Role::Transmit) => {
+                    self.transmitter.receive_byte(byte)?
+                }
+                (Err(ref e), Role::Receive) if is_timeout(e) => {
+                    self.receiver.timed_out()?
+                }
+                (Err(ref e), Role::Transmit) if is_timeout(e) => {
+                    self.transmitter.timed_out()?
+                }
+                (Err(e), _) => return Err(e),
+            }
+        }
     }
 
-    /// Reads a single byte from the inner I/O stream and compares it to `byte`.
-    /// If the bytes match, the byte is returned as an `Ok`. If they differ and
-    /// the read byte is not `CAN`, an error of `InvalidData` with the message
-    /// `expected` is returned. If they differ and the read byte is `CAN`, an
-    /// error of `ConnectionAborted` is returned. In either case, if they bytes
-    /// differ, a `CAN` byte is written out to the inner stream.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if reading from the inner stream fails, if the read
-    /// byte was not `byte`, if the read byte was `CAN` and `byte` is not `CAN`,
-    /// or if writing the `CAN` byte failed on byte mismatch.
-    fn expect_byte_or_cancel(
-        &mut self,
-        byte: u8,
-        msg: &'static str,
-    ) -> io::Result<u8> {
-        match self.expect_byte(byte, msg) {
-            Err(e) => {
-                self.write_byte(CAN)?;
-                Err(e)
+    /// Updates the transfer statistics for `event` and reports it to the
+    /// progress callback.
+    fn report(&mut self, event: Event) {
+        match event {
+            Event::Started => {
+                self.started_at = Some((self.config.clock)());
+                (self.progress)(Progress::Started);
+            }
+            Event::PacketReceived { packet, len }
+            | Event::PacketSent { packet, len } => {
+                self.stats.bytes += len;
+                self.stats.packets += 1;
+                let stats = self.stats();
+                (self.progress)(Progress::Packet {
+                    packet,
+                    size: len,
+                    stats,
+                });
+            }
+            Event::Retry { packet, reason } => {
+                self.stats.retries += 1;
+                let stats = self.stats();
+                (self.progress)(Progress::Retry {
+                    packet,
+                    reason,
+                    stats,
+                });
+            }
+            Event::Terminated => {
+                let stats = self.stats();
+                (self.progress)(Progress::Terminated { stats });
             }
-            Ok(byte) => Ok(byte),
+            Event::Cancelled(reason) => {
+                let stats = self.stats();
+                (self.progress)(Progress::Cancelled { reason, stats });
+            }
+            Event::Done | Event::Aborted { .. } => {}
         }
     }
 
-    /// Reads a single byte from the inner I/O stream and compares it to `byte`.
-    /// If they differ, an error of `InvalidData` with the message `expected` is
-    /// returned. Otherwise the byte is returned. If `byte` is not `CAN` and the
-    /// read byte is `CAN`, a `ConnectionAborted` error is returned.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if reading from the inner stream fails, or if the read
-    /// byte was not `byte`. If the read byte differed and was `CAN`, an error
-    /// of `ConnectionAborted` is returned. Otherwise, the error kind is
-    /// `InvalidData`.
-    fn expect_byte(
-        &mut self,
-        byte: u8,
-        expected: &'static str,
-    ) -> io::Result<u8> {
-        match self.read_byte(false).expect("read_byte failed") {
-            b if b == byte => Ok(byte),
-            CAN => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "received CAN",
-            )),
-            _ => Err(io::Error::new(io::ErrorKind::InvalidData, expected)),
+    /// Returns a snapshot of the transfer statistics.
+    fn stats(&self) -> Stats {
+        let elapsed = match self.started_at {
+            Some(at) => (self.config.clock)().wrapping_sub(at),
+            None => 0,
+        };
+
+        Stats {
+            elapsed,
+            ..self.stats
         }
     }
 
     /// Reads (downloads) a single packet from the inner stream using the XMODEM
-    /// protocol. On success, returns the number of bytes read (always 128).
+    /// protocol. On success, returns the number of bytes read: 128 for an
+    /// `SOH` packet or 1024 for an `STX` (XMODEM-1K) packet.
     ///
-    /// The progress callback is called with `Progress::Start` when reception
-    /// for the first packet has started and subsequently with
-    /// `Progress::Packet` when a packet is received successfully.
+    /// Reception of the first packet starts by requesting the checksum given
+    /// by the session's [`XmodemConfig`], falling back to
+    /// `Checksum::Standard` if the sender does not answer `C` probes. See
+    /// [`Receiver`] for the details of the protocol.
+    ///
+    /// The progress callback is called with `Progress::Started` when the
+    /// sender first responds and subsequently with `Progress::Packet` when a
+    /// packet is received successfully, `Progress::Retry` when a packet is
+    /// requested again or `Progress::Terminated` when end of transmission is
+    /// received. `Progress::Cancelled` is reported whenever `CAN` is sent or
+    /// received.
     ///
     /// # Errors
     ///
@@ -266,16 +431,25 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// point. Also returns an error if the XMODEM protocol indicates an error.
     /// In particular, an `InvalidData` error is returned when:
     ///
-    ///   * The sender's first byte for a packet isn't `EOT` or `SOH`.
+    ///   * The sender's first byte for a packet isn't `EOT`, `SOH` or `STX`.
     ///   * The sender doesn't send a second `EOT` after the first.
     ///   * The received packet numbers don't match the expected values.
     ///
-    /// An error of kind `Interrupted` is returned if a packet checksum fails.
+    /// An error of kind `Interrupted` is returned if a packet checksum fails or
+    /// the packet times out after reception started. `NAK` has then been sent
+    /// to request the packet again. An error of kind `BrokenPipe` is returned
+    /// and the session is cancelled once a packet was requested
+    /// `config.max_retries` times.
+    ///
+    /// An error of kind `TimedOut` is returned if the sender does not respond
+    /// to any of the `config.max_retries` initial requests.
     ///
     /// An error of kind `ConnectionAborted` is returned if a `CAN` byte is
     /// received when not expected.
     ///
-    /// An error of kind `UnexpectedEof` is returned if `buf.len() < 128`.
+    /// An error of kind `UnexpectedEof` is returned if `buf.len() < 128`, or
+    /// if `buf.len() < 1024` and the sender starts an `STX` packet. In the
+    /// latter case a `CAN` byte is written out to the inner stream.
     pub fn read_packet(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if buf.len() < BUF_LEN {
             return Err(io::Error::new(
@@ -284,63 +458,19 @@ impl<T: io::Read + io::Write> Xmodem<T> {
             ));
         }
 
-        if !self.started {
-            self.write_byte(NAK)?;
-            self.started = true;
-            (self.progress)(Progress::Started);
-        }
-
-        match self.read_byte(true)? {
-            EOT => {
-                (self.progress)(Progress::Terminated(self.packet));
-                self.write_byte(NAK)?;
-                self.expect_byte(EOT, "Expected a second EOT")?;
-                self.write_byte(ACK)?;
-                Ok(0)
+        self.receiver.set_accept_1k(buf.len() >= BUF_LEN_1K);
+        self.drive(Role::Receive, |xmodem, event| match event {
+            Event::PacketReceived { len, .. } => {
+                buf[..len].copy_from_slice(xmodem.receiver.data());
+                Some(Ok(len))
             }
-            SOH => {
-                let packet = self.packet;
-                let complement = 255 - packet;
-
-                self.expect_byte_or_cancel(
-                    packet,
-                    "Expected a different packet",
-                )?;
-                self.expect_byte_or_cancel(
-                    complement,
-                    "Expected the complement",
-                )?;
-
-                for i in 0..128 {
-                    // CAN is allowed here
-                    let byte = self.read_byte(false)?;
-                    buf[i] = byte;
-                }
-
-                let checksum =
-                    (buf.iter().fold(0u16, |a, &b| a + b as u16) % 256) as u8;
-
-                match self.expect_byte(checksum, "checksum mismatch") {
-                    Ok(_) => {
-                        self.write_byte(ACK)?;
-                        self.packet = self.packet.wrapping_add(1);
-                        (self.progress)(Progress::Packet(self.packet));
-                        Ok(BUF_LEN)
-                    }
-                    _ => {
-                        self.write_byte(NAK)?;
-                        Err(io::Error::new(
-                            io::ErrorKind::Interrupted,
-                            "checksum mismatch",
-                        ))
-                    }
-                }
+            Event::Done => Some(Ok(0)),
+            Event::Retry { reason, .. } => Some(Err(retry_error(reason))),
+            Event::Aborted { kind, msg } => {
+                Some(Err(io::Error::new(kind, msg)))
             }
-            _ => Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Neither SOH nor EOT recieved",
-            )),
-        }
+            _ => None,
+        })
     }
 
     /// Sends (uploads) a single packet to the inner stream using the XMODEM
@@ -349,10 +479,20 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// transmission is complete. On success, returns the number of bytes
     /// written.
     ///
+    /// If `buf.len() >= 1024`, the first 1024 bytes of `buf` are sent as an
+    /// `STX` (XMODEM-1K) packet. Otherwise, the first 128 bytes of `buf` are
+    /// sent as an `SOH` packet.
+    ///
+    /// The packet's checksum is the one requested by the receiver's first byte:
+    /// `Checksum::Standard` for `NAK` and `Checksum::Crc16` for `C`. See
+    /// [`Transmitter`] for the details of the protocol.
+    ///
     /// The progress callback is called with `Progress::Waiting` before waiting
-    /// for the receiver's `NAK`, `Progress::Start` when transmission of the
+    /// for the receiver's `NAK`, `Progress::Started` when transmission of the
     /// first packet has started and subsequently with `Progress::Packet` when a
-    /// packet is sent successfully.
+    /// packet is sent successfully, `Progress::Retry` when a packet is
+    /// rejected or `Progress::Terminated` when end of transmission is sent.
+    /// `Progress::Cancelled` is reported whenever `CAN` is sent or received.
     ///
     /// # Errors
     ///
@@ -360,7 +500,7 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// point. Also returns an error if the XMODEM protocol indicates an error.
     /// In particular, an `InvalidData` error is returned when:
     ///
-    ///   * The receiver's first byte isn't a `NAK`.
+    ///   * The receiver's first byte isn't a `NAK` or `C`.
     ///   * The receiver doesn't respond with a `NAK` to the first `EOT`.
     ///   * The receiver doesn't respond with an `ACK` to the second `EOT`.
     ///   * The receiver responds to a complete packet with something besides
@@ -372,7 +512,14 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// An error of kind `ConnectionAborted` is returned if a `CAN` byte is
     /// received when not expected.
     ///
-    /// An error of kind `Interrupted` is returned if a packet checksum fails.
+    /// An error of kind `Interrupted` is returned if the receiver rejects the
+    /// packet or its acknowledgement times out. The packet should then be
+    /// written again. An error of kind `BrokenPipe` is returned and the
+    /// session is cancelled once a packet was rejected `config.max_retries`
+    /// times.
+    ///
+    /// An error of kind `TimedOut` is returned if the receiver does not start
+    /// the session within `config.initial_nak_wait`.
     pub fn write_packet(&mut self, buf: &[u8]) -> io::Result<usize> {
         if buf.len() != 0 && buf.len() < BUF_LEN {
             return Err(io::Error::new(
@@ -381,51 +528,228 @@ impl<T: io::Read + io::Write> Xmodem<T> {
             ));
         }
 
-        if !self.started {
+        if !self.transmitter.is_started() {
             (self.progress)(Progress::Waiting);
-            self.expect_byte(NAK, "transmission must start with NAK")?;
-            self.started = true;
-            (self.progress)(Progress::Started);
         }
 
-        if buf.len() == 0 {
-            (self.progress)(Progress::Terminated(self.packet));
-            self.write_byte(EOT)?;
-            self.expect_byte(NAK, "Expected NAK after first EOT")?;
-            self.write_byte(EOT)?;
-            self.expect_byte(ACK, "Expected ACK after second EOT")?;
-            Ok(0)
-        } else {
-            let packet = self.packet;
-            let complement = 255 - self.packet;
-
-            let checksum =
-                (buf.iter().fold(0u16, |a, &b| a + b as u16) % 256) as u8;
-
-            self.write_byte(SOH)?;
-            self.write_byte(packet)?;
-            self.write_byte(complement)?;
-
-            for byte in buf.into_iter() {
-                self.write_byte(*byte)?;
+        match buf.len() {
+            0 => self.transmitter.finish()?,
+            n if n >= BUF_LEN_1K => self.transmitter.send(&buf[..BUF_LEN_1K])?,
+            _ => self.transmitter.send(&buf[..BUF_LEN])?,
+        }
+
+        self.drive(Role::Transmit, |_, event| match event {
+            Event::PacketSent { len, .. } => Some(Ok(len)),
+            Event::Done => Some(Ok(0)),
+            Event::Retry { reason, .. } => Some(Err(retry_error(reason))),
+            Event::Aborted { kind, msg } => {
+                Some(Err(io::Error::new(kind, msg)))
+            }
+            _ => None,
+        })
+    }
+
+    /// Transmits all of `data` using `write_packet`, followed by end of
+    /// transmission. Data is padded with `config.padding` to a multiple of 128
+    /// bytes. Returns the number of bytes read from `data`.
+    ///
+    /// If `len` is `Some`, `data` must yield exactly `len` bytes. Otherwise,
+    /// the session is cancelled as soon as the difference is noticed, before
+    /// any excess data or end of transmission is sent.
+    ///
+    /// See [`Xmodem::transmit_with_progress()`] for how block sizes are chosen.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from `data` fails, if a packet could not be
+    /// written after `config.max_retries` attempts, or if `write_packet` fails
+    /// for any other reason. An error of kind `InvalidData` is returned if
+    /// `data` does not yield `len` bytes.
+    pub(crate) fn send_data<R: ByteRead>(
+        &mut self,
+        mut data: R,
+        len: Option<usize>,
+    ) -> io::Result<usize> {
+        let mut packet = [0u8; BUF_LEN_1K];
+        let mut written = 0;
+        let (mut use_1k, mut accepted_1k) = (true, false);
+        loop {
+            let n = data.read_bytes(&mut packet)?;
+            let padding = self.config.padding;
+            packet[n..].iter_mut().for_each(|b| *b = padding);
+
+            let mismatch = match len {
+                Some(len) => written + n > len || (n == 0 && written < len),
+                None => false,
+            };
+            if mismatch {
+                let reason = "file length differs from its header";
+                return self.cancel_transmit(reason);
+            }
+
+            if n == 0 {
+                self.write_packet(&[])?;
+                return Ok(written);
+            }
+
+            if use_1k && n == BUF_LEN_1K {
+                use_1k = self.write_1k_packet(&packet, accepted_1k)?;
+                if use_1k {
+                    accepted_1k = true;
+                    written += n;
+                    continue;
+                }
+            }
+
+            let padded = (n + BUF_LEN - 1) / BUF_LEN * BUF_LEN;
+            for chunk in packet[..padded].chunks(BUF_LEN) {
+                self.write_packet_with_retry(chunk)?;
+            }
+
+            written += n;
+        }
+    }
+
+    /// Sends the 1024 byte packet `buf` using `write_packet`, writing it again
+    /// whenever the receiver rejects it or its acknowledgement times out.
+    /// Unless `accepted`, i.e. the receiver has acknowledged a 1024 byte packet
+    /// before, gives up once the packet was rejected `MAX_1K_REJECTIONS` times
+    /// in a row. Returns `true` if the packet was sent and `false` if the
+    /// receiver appears not to support XMODEM-1K, in which case the 128 byte
+    /// packets sent in its place get the full `config.max_retries` attempts.
+    ///
+    /// # Errors
+    ///
+    /// Returns any other error from `write_packet`.
+    fn write_1k_packet(
+        &mut self,
+        buf: &[u8],
+        accepted: bool,
+    ) -> io::Result<bool> {
+        let mut rejections = 0;
+        loop {
+            match self.write_packet(buf) {
+                Ok(_) => return Ok(true),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    rejections += 1;
+                    if !accepted && rejections == MAX_1K_REJECTIONS {
+                        return Ok(false);
+                    }
+                }
+                Err(e) => return Err(e),
             }
+        }
+    }
 
-            self.write_byte(checksum)?;
+    /// Cancels the transmission because of `reason`, sending `CAN`. Always
+    /// returns an error of kind `InvalidData` described by `reason`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the inner stream fails.
+    fn cancel_transmit(&mut self, reason: &'static str) -> io::Result<usize> {
+        self.transmitter.cancel(reason)?;
+        self.drive(Role::Transmit, |_, event| match event {
+            Event::Cancelled(_) => Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                reason,
+            ))),
+            _ => None,
+        })
+    }
 
-            match self.read_byte(true)? {
-                ACK => {
-                    (self.progress)(Progress::Packet(self.packet));
-                    self.packet = self.packet.wrapping_add(1);
-                    Ok(128)
+    /// Receives packets using `read_packet` until end of transmission, writing
+    /// their data into `into`. If `limit` is `Some`, at most `limit` bytes are
+    /// written and the remaining (padding) bytes are discarded. Otherwise, if
+    /// `config.trim` is `Trim::Padding`, trailing `config.padding` bytes are
+    /// held back until a packet with other data follows and are discarded at
+    /// end of transmission. Returns the number of bytes written to `into`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `into` fails, if a packet could not be
+    /// read after `config.max_retries` attempts, or if `read_packet` fails for
+    /// any other reason.
+    pub(crate) fn receive_data<W: ByteWrite>(
+        &mut self,
+        mut into: W,
+        limit: Option<usize>,
+    ) -> io::Result<usize> {
+        let mut packet = [0u8; BUF_LEN_1K];
+        let (mut received, mut held) = (0, 0);
+        let (padding, trim) = (self.config.padding, self.config.trim);
+        loop {
+            let n = match self.read_packet(&mut packet) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Err(e) => return Err(e),
+                Ok(0) => break,
+                Ok(n) => n,
+            };
+
+            let n = match limit {
+                Some(limit) => cmp::min(n, limit - received),
+                None => n,
+            };
+            received += n;
+
+            let end = match (trim, limit) {
+                (Trim::Padding, None) => packet[..n]
+                    .iter()
+                    .rposition(|b| *b != padding)
+                    .map_or(0, |i| i + 1),
+                _ => n,
+            };
+            if end > 0 {
+                for _ in 0..held {
+                    into.write_byte(padding)?;
                 }
-                NAK => Err(io::Error::new(
-                    io::ErrorKind::Interrupted,
-                    "checksum mismatch",
-                )),
-                _ => Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Expected ACK or NAK after packet transmission",
-                )),
+                into.write_bytes(&packet[..end])?;
+                held = 0;
+            }
+
+            held += n - end;
+        }
+
+        Ok(received - held)
+    }
+
+    /// Resets the session so that the next packet read or written is packet
+    /// number `packet`, starting with the receiver's `NAK` or `C` handshake.
+    #[cfg(feature = "std")]
+    pub(crate) fn restart(&mut self, packet: u8) {
+        self.receiver.restart(packet);
+        self.transmitter.restart(packet);
+        self.started_at = None;
+    }
+
+    /// Resets the transfer statistics for a new transfer of `total` bytes.
+    #[cfg(feature = "std")]
+    pub(crate) fn reset_stats(&mut self, total: Option<usize>) {
+        self.stats = Stats {
+            total,
+            ..Stats::default()
+        };
+    }
+
+    /// Sends a single packet using `write_packet`, writing it again whenever
+    /// the receiver rejects it or its acknowledgement times out. Returns the
+    /// number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns any other error from `write_packet`. An error of kind
+    /// `BrokenPipe` is returned and the session is cancelled if the packet
+    /// was rejected `config.max_retries` times.
+    pub(crate) fn write_packet_with_retry(
+        &mut self,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        loop {
+            match self.write_packet(buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                result => return result,
             }
         }
     }
@@ -439,3 +763,23 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     /// errors or EOF being reached.
     pub fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
 }
+
+/// Returns the error of kind `Interrupted` reported for a packet that must be
+/// sent/requested again because of `reason`.
+fn retry_error(reason: RetryReason) -> io::Error {
+    let msg = match reason {
+        RetryReason::Nak => "packet rejected",
+        RetryReason::Checksum => "checksum mismatch",
+        RetryReason::Timeout => "timed out waiting for a byte",
+    };
+
+    io::Error::new(io::ErrorKind::Interrupted, msg)
+}
+
+/// Returns `true` if `e` indicates that no data arrived in time.
+fn is_timeout(e: &io::Error) -> bool {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,973 @@
+//! Sans-IO implementation of the XMODEM protocol.
+//!
+//! A [`Receiver`] or [`Transmitter`] never performs I/O itself. Bytes read
+//! from the other side are fed in with `receive_byte`, an expired timeout is
+//! reported with `timed_out`, bytes to send are taken from `poll_transmit`
+//! and acknowledged with `transmitted`, and the outcome of each step is
+//! reported as an [`Event`] by `poll_event`. This makes the protocol usable
+//! from interrupt handlers and event loops; [`Xmodem`] drives these machines
+//! with blocking I/O.
+//!
+//! Nothing is dropped: a call feeding a machine fails with an error of kind
+//! `WouldBlock`, before doing anything, if the events and output of earlier
+//! calls leave no room for its own.
+
+use checksum::Checksum;
+use config::XmodemConfig;
+use io;
+use progress::{CancelReason, RetryReason};
+use {ACK, BUF_LEN, BUF_LEN_1K, CAN, CRC, EOT, NAK, SOH, STX};
+
+/// Something that happened while running a [`Receiver`] or [`Transmitter`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The other side responded for the first time.
+    Started,
+    /// Packet `packet` holding `len` bytes of data was received and
+    /// acknowledged. The data is available from [`Receiver::data()`] until
+    /// the next byte is fed in.
+    PacketReceived { packet: u8, len: usize },
+    /// Packet `packet` holding `len` bytes of data was acknowledged. The
+    /// transmitter is ready for the next packet.
+    PacketSent { packet: u8, len: usize },
+    /// Packet `packet` is requested again because of `reason`, or must be
+    /// sent again because of `reason`.
+    Retry { packet: u8, reason: RetryReason },
+    /// End of transmission was sent/received.
+    Terminated,
+    /// The end of transmission handshake completed. The session is over.
+    Done,
+    /// The session was cancelled with `CAN` because of `reason`.
+    Cancelled(CancelReason),
+    /// The session failed with an error of kind `kind` described by `msg`.
+    /// The session is over.
+    Aborted { kind: io::ErrorKind, msg: &'static str },
+}
+
+/// The most events a single call feeding a machine produces.
+const STEP_EVENTS: usize = 3;
+
+/// The most bytes other than a packet a single call feeding a machine
+/// queues. A packet replaces whatever output is pending.
+const STEP_OUTPUT: usize = 2;
+
+/// The number of events a machine holds until they are polled.
+const EVENTS: usize = 4;
+
+/// Events waiting to be polled.
+struct Events {
+    queue: [Option<Event>; EVENTS],
+    head:  usize,
+    len:   usize,
+}
+
+impl Events {
+    fn new() -> Events {
+        Events {
+            queue: [None; EVENTS],
+            head:  0,
+            len:   0,
+        }
+    }
+
+    /// Queues `event`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock` if `EVENTS` events are waiting
+    /// to be polled.
+    fn push(&mut self, event: Event) -> io::Result<()> {
+        if self.len == EVENTS {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "events were not polled",
+            ));
+        }
+
+        self.queue[(self.head + self.len) % EVENTS] = Some(event);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.queue[self.head].take();
+        self.head = (self.head + 1) % EVENTS;
+        self.len -= 1;
+        event
+    }
+
+    /// Returns the number of events that can be queued.
+    fn room(&self) -> usize { EVENTS - self.len }
+}
+
+/// Bytes waiting to be sent: at most one packet with its header and a
+/// 2 byte checksum.
+struct Outbox {
+    buf: [u8; BUF_LEN_1K + 5],
+    len: usize,
+    pos: usize,
+}
+
+impl Outbox {
+    fn new() -> Outbox {
+        Outbox {
+            buf: [0; BUF_LEN_1K + 5],
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.pos = 0;
+    }
+
+    /// Discards the pending output and queues `byte` instead.
+    fn reset(&mut self, byte: u8) {
+        self.buf[0] = byte;
+        self.len = 1;
+        self.pos = 0;
+    }
+
+    /// Queues `bytes` after the pending output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock` if the pending output leaves no
+    /// room for `bytes`.
+    fn push(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.pos == self.len {
+            self.clear();
+        }
+
+        if bytes.len() > self.buf.len() - self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "output was not transmitted",
+            ));
+        }
+
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn pending(&self) -> &[u8] { &self.buf[self.pos..self.len] }
+
+    fn advance(&mut self, n: usize) {
+        self.pos = ::core::cmp::min(self.pos + n, self.len);
+    }
+
+    /// Returns the number of bytes that can be queued.
+    fn room(&self) -> usize {
+        match self.pos == self.len {
+            true => self.buf.len(),
+            false => self.buf.len() - self.len,
+        }
+    }
+}
+
+/// State shared by both machines: the configuration, pending output and
+/// pending events.
+struct Channel {
+    config: XmodemConfig,
+    out:    Outbox,
+    events: Events,
+}
+
+impl Channel {
+    fn new(config: XmodemConfig) -> Channel {
+        Channel {
+            config,
+            out: Outbox::new(),
+            events: Events::new(),
+        }
+    }
+
+    /// Checks that a call feeding the machine can queue all of its events
+    /// and output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock` if too many events of earlier
+    /// calls haven't been polled or too much of their output hasn't been
+    /// transmitted.
+    fn reserve(&self) -> io::Result<()> {
+        if self.events.room() < STEP_EVENTS {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "events were not polled",
+            ));
+        }
+
+        if self.out.room() < STEP_OUTPUT {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "output was not transmitted",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Queues `CAN`, or two `CAN` bytes if `config.double_can` is set, and
+    /// reports the cancellation because of `reason`.
+    fn cancel(&mut self, reason: &'static str) -> io::Result<()> {
+        match self.config.double_can {
+            true => self.out.push(&[CAN, CAN])?,
+            false => self.out.push(&[CAN])?,
+        }
+
+        self.events.push(Event::Cancelled(CancelReason::Sent(reason)))
+    }
+
+    /// Reports that the other side cancelled the session.
+    fn cancelled(&mut self) -> io::Result<()> {
+        self.events.push(Event::Cancelled(CancelReason::Received))?;
+        self.abort(io::ErrorKind::ConnectionAborted, "received CAN")
+    }
+
+    /// Reports that the session failed without cancelling it.
+    fn abort(
+        &mut self,
+        kind: io::ErrorKind,
+        msg: &'static str,
+    ) -> io::Result<()> {
+        self.events.push(Event::Aborted { kind, msg })
+    }
+
+    /// Cancels the session and reports that it failed.
+    fn fail(
+        &mut self,
+        kind: io::ErrorKind,
+        msg: &'static str,
+    ) -> io::Result<()> {
+        self.cancel(msg)?;
+        self.abort(kind, msg)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RxState {
+    Probing,
+    Header,
+    Packet,
+    Complement,
+    Data,
+    Checksum,
+    SecondEot,
+    Done,
+    Aborted,
+}
+
+/// The receiving (downloading) side of an XMODEM session.
+///
+/// A new receiver immediately has a `C` or `NAK` to send, requesting the
+/// checksum given by its [`XmodemConfig`]. Unanswered requests are repeated
+/// up to `config.max_retries` times in total, falling back to
+/// `Checksum::Standard` after `config.crc_probes` unanswered `C` probes.
+/// Every received packet is acknowledged; packets with a bad checksum or
+/// that time out are requested again with `NAK` up to `config.max_retries`
+/// times before the session is cancelled.
+pub struct Receiver {
+    chan:      Channel,
+    checksum:  Checksum,
+    state:     RxState,
+    packet:    u8,
+    attempts:  usize,
+    probes:    usize,
+    accept_1k: bool,
+    buf:       [u8; BUF_LEN_1K],
+    len:       usize,
+    pos:       usize,
+    received:  [u8; 2],
+}
+
+impl Receiver {
+    /// Returns a new `Receiver` using the session configuration `config`,
+    /// expecting packet `1` first.
+    pub fn new(config: XmodemConfig) -> Receiver {
+        let mut receiver = Receiver {
+            chan:      Channel::new(config),
+            checksum:  config.checksum,
+            state:     RxState::Probing,
+            packet:    1,
+            attempts:  0,
+            probes:    0,
+            accept_1k: true,
+            buf:       [0; BUF_LEN_1K],
+            len:       0,
+            pos:       0,
+            received:  [0; 2],
+        };
+
+        receiver.restart(1);
+        receiver
+    }
+
+    /// Restarts reception with the `C` or `NAK` handshake, expecting packet
+    /// number `packet` next. Pending output is discarded. The checksum
+    /// negotiated so far is kept.
+    pub fn restart(&mut self, packet: u8) {
+        self.state = RxState::Probing;
+        self.packet = packet;
+        self.attempts = 0;
+        self.probes = 1;
+        let request = self.request();
+        self.chan.out.reset(request);
+    }
+
+    /// Sets whether `STX` (XMODEM-1K) packets are accepted. If not, an `STX`
+    /// packet cancels the session with an error of kind `UnexpectedEof`.
+    pub fn set_accept_1k(&mut self, accept: bool) { self.accept_1k = accept; }
+
+    /// Returns the checksum currently requested from the sender.
+    pub fn checksum(&self) -> Checksum { self.checksum }
+
+    /// Returns `true` once the session is over, either because end of
+    /// transmission was received or because it was aborted.
+    pub fn is_finished(&self) -> bool {
+        self.state == RxState::Done || self.state == RxState::Aborted
+    }
+
+    /// Returns how long to wait for the next byte in microseconds before
+    /// calling `timed_out`, or `None` if there is no limit.
+    pub fn timeout(&self) -> Option<u64> { self.chan.config.byte_timeout }
+
+    /// Returns the data of the packet last reported by
+    /// `Event::PacketReceived`.
+    pub fn data(&self) -> &[u8] { &self.buf[..self.len] }
+
+    /// Returns the bytes waiting to be sent to the sender.
+    pub fn poll_transmit(&self) -> &[u8] { self.chan.out.pending() }
+
+    /// Marks the first `n` bytes returned by `poll_transmit` as sent.
+    pub fn transmitted(&mut self, n: usize) { self.chan.out.advance(n) }
+
+    /// Returns the next event, if any. Events should be polled after every
+    /// call feeding the receiver; at most four are held.
+    pub fn poll_event(&mut self) -> Option<Event> { self.chan.events.pop() }
+
+    /// Cancels the session because of `reason`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock`, without cancelling the session,
+    /// if the events or output of earlier calls haven't been taken with
+    /// `poll_event` and `transmitted`.
+    pub fn cancel(&mut self, reason: &'static str) -> io::Result<()> {
+        self.chan.reserve()?;
+        if !self.is_finished() {
+            self.chan.cancel(reason)?;
+            self.state = RxState::Aborted;
+        }
+
+        Ok(())
+    }
+
+    /// Feeds a byte received from the sender.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock`, without consuming `byte`, if
+    /// the events or output of earlier calls haven't been taken with
+    /// `poll_event` and `transmitted`.
+    pub fn receive_byte(&mut self, byte: u8) -> io::Result<()> {
+        self.chan.reserve()?;
+        match self.state {
+            RxState::Probing if byte == CAN => self.cancelled()?,
+            RxState::Probing => {
+                self.chan.events.push(Event::Started)?;
+                self.header(byte)?;
+            }
+            RxState::Header if byte == CAN => self.cancelled()?,
+            RxState::Header => self.header(byte)?,
+            RxState::Packet => match byte == self.packet {
+                true => self.state = RxState::Complement,
+                false => {
+                    self.unexpected(byte, "Expected a different packet")?
+                }
+            },
+            RxState::Complement => match byte == 255 - self.packet {
+                true => {
+                    self.state = RxState::Data;
+                    self.pos = 0;
+                }
+                false => self.unexpected(byte, "Expected the complement")?,
+            },
+            // CAN is allowed in both the data and the checksum
+            RxState::Data => {
+                self.buf[self.pos] = byte;
+                self.pos += 1;
+                if self.pos == self.len {
+                    self.state = RxState::Checksum;
+                    self.pos = 0;
+                }
+            }
+            RxState::Checksum => {
+                self.received[self.pos] = byte;
+                self.pos += 1;
+                if self.pos == self.checksum.size() {
+                    self.check()?;
+                }
+            }
+            RxState::SecondEot => match byte {
+                EOT => {
+                    self.chan.out.push(&[ACK])?;
+                    self.chan.events.push(Event::Done)?;
+                    self.state = RxState::Done;
+                }
+                CAN => self.cancelled()?,
+                _ => self.abort(
+                    io::ErrorKind::InvalidData,
+                    "Expected a second EOT",
+                )?,
+            },
+            RxState::Done | RxState::Aborted => {}
+        }
+
+        Ok(())
+    }
+
+    /// Reports that no byte arrived within `timeout()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock`, without handling the timeout,
+    /// if the events or output of earlier calls haven't been taken with
+    /// `poll_event` and `transmitted`.
+    pub fn timed_out(&mut self) -> io::Result<()> {
+        self.chan.reserve()?;
+        let max_retries = self.chan.config.max_retries;
+        match self.state {
+            RxState::Probing if self.probes < max_retries => {
+                if self.checksum == Checksum::Crc16
+                    && self.probes >= self.chan.config.crc_probes
+                {
+                    self.checksum = Checksum::Standard;
+                }
+
+                self.probe()?;
+            }
+            RxState::Probing => self.abort(
+                io::ErrorKind::TimedOut,
+                "timed out waiting for the sender",
+            )?,
+            RxState::Done | RxState::Aborted => {}
+            _ => self.reject(RetryReason::Timeout)?,
+        }
+
+        Ok(())
+    }
+
+    /// Returns the byte requesting the current checksum from the sender.
+    fn request(&self) -> u8 {
+        match self.checksum {
+            Checksum::Crc16 => CRC,
+            Checksum::Standard => NAK,
+        }
+    }
+
+    /// Requests the current checksum from the sender.
+    fn probe(&mut self) -> io::Result<()> {
+        self.probes += 1;
+        let request = self.request();
+        self.chan.out.push(&[request])
+    }
+
+    /// Handles the first byte of a packet or end of transmission.
+    fn header(&mut self, byte: u8) -> io::Result<()> {
+        self.state = RxState::Packet;
+        match byte {
+            EOT => {
+                self.chan.out.push(&[NAK])?;
+                self.chan.events.push(Event::Terminated)?;
+                self.state = RxState::SecondEot;
+            }
+            SOH => self.len = BUF_LEN,
+            STX if !self.accept_1k => self.fail(
+                io::ErrorKind::UnexpectedEof,
+                "Buffer too small for a 1K packet",
+            )?,
+            STX => self.len = BUF_LEN_1K,
+            _ => self.abort(
+                io::ErrorKind::InvalidData,
+                "Neither SOH, STX nor EOT recieved",
+            )?,
+        }
+
+        Ok(())
+    }
+
+    /// Acknowledges the packet if its checksum matches and requests it again
+    /// otherwise.
+    fn check(&mut self) -> io::Result<()> {
+        let size = self.checksum.size();
+        let mut expected = [0u8; 2];
+        self.checksum.compute(&self.buf[..self.len], &mut expected);
+        if expected[..size] != self.received[..size] {
+            return self.reject(RetryReason::Checksum);
+        }
+
+        self.chan.out.push(&[ACK])?;
+        self.chan.events.push(Event::PacketReceived {
+            packet: self.packet,
+            len:    self.len,
+        })?;
+        self.packet = self.packet.wrapping_add(1);
+        self.attempts = 0;
+        self.state = RxState::Header;
+        Ok(())
+    }
+
+    /// Requests the current packet again because of `reason`, or cancels the
+    /// session if it was requested `config.max_retries` times.
+    fn reject(&mut self, reason: RetryReason) -> io::Result<()> {
+        self.attempts += 1;
+        if self.attempts >= self.chan.config.max_retries {
+            return self.fail(io::ErrorKind::BrokenPipe, "bad receive");
+        }
+
+        self.chan.out.push(&[NAK])?;
+        self.chan.events.push(Event::Retry {
+            packet: self.packet,
+            reason,
+        })?;
+        self.state = RxState::Header;
+        Ok(())
+    }
+
+    /// Handles `byte` when another byte was expected.
+    fn unexpected(&mut self, byte: u8, msg: &'static str) -> io::Result<()> {
+        match byte {
+            CAN => self.cancelled(),
+            _ => self.fail(io::ErrorKind::InvalidData, msg),
+        }
+    }
+
+    fn cancelled(&mut self) -> io::Result<()> {
+        self.state = RxState::Aborted;
+        self.chan.cancelled()
+    }
+
+    fn abort(
+        &mut self,
+        kind: io::ErrorKind,
+        msg: &'static str,
+    ) -> io::Result<()> {
+        self.state = RxState::Aborted;
+        self.chan.abort(kind, msg)
+    }
+
+    fn fail(
+        &mut self,
+        kind: io::ErrorKind,
+        msg: &'static str,
+    ) -> io::Result<()> {
+        self.state = RxState::Aborted;
+        self.chan.fail(kind, msg)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TxState {
+    Waiting,
+    Ready,
+    AwaitAck,
+    Rejected,
+    FirstEot,
+    SecondEot,
+    Done,
+    Aborted,
+}
+
+/// What a transmitter sends once the receiver starts the session.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Queued {
+    Nothing,
+    Packet,
+    Eot,
+}
+
+/// The transmitting (uploading) side of an XMODEM session.
+///
+/// The transmitter waits for the receiver's `NAK` or `C`, which selects the
+/// checksum, before sending anything. Packets are queued with `send` and end
+/// of transmission with `finish`, either before or after the receiver
+/// starts the session. A rejected packet is reported with `Event::Retry`
+/// and must be sent again with `resend` or `send`; after
+/// `config.max_retries` rejections the session is cancelled.
+pub struct Transmitter {
+    chan:     Channel,
+    checksum: Checksum,
+    state:    TxState,
+    queued:   Queued,
+    packet:   u8,
+    attempts: usize,
+    data:     [u8; BUF_LEN_1K],
+    len:      usize,
+}
+
+impl Transmitter {
+    /// Returns a new `Transmitter` using the session configuration `config`,
+    /// sending packet `1` first.
+    pub fn new(config: XmodemConfig) -> Transmitter {
+        Transmitter {
+            chan:     Channel::new(config),
+            checksum: Checksum::Standard,
+            state:    TxState::Waiting,
+            queued:   Queued::Nothing,
+            packet:   1,
+            attempts: 0,
+            data:     [0; BUF_LEN_1K],
+            len:      0,
+        }
+    }
+
+    /// Restarts transmission with the receiver's `NAK` or `C` handshake,
+    /// sending packet number `packet` next. Pending output is discarded.
+    pub fn restart(&mut self, packet: u8) {
+        self.state = TxState::Waiting;
+        self.queued = Queued::Nothing;
+        self.packet = packet;
+        self.attempts = 0;
+        self.chan.out.clear();
+    }
+
+    /// Returns the checksum requested by the receiver.
+    pub fn checksum(&self) -> Checksum { self.checksum }
+
+    /// Returns `true` once the receiver has started the session.
+    pub fn is_started(&self) -> bool { self.state != TxState::Waiting }
+
+    /// Returns `true` if a packet or end of transmission can be queued: no
+    /// packet is in flight and the session is not over.
+    pub fn is_ready(&self) -> bool {
+        match self.state {
+            TxState::Waiting => self.queued == Queued::Nothing,
+            TxState::Ready | TxState::Rejected => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` once the session is over, either because end of
+    /// transmission was acknowledged or because it was aborted.
+    pub fn is_finished(&self) -> bool {
+        self.state == TxState::Done || self.state == TxState::Aborted
+    }
+
+    /// Returns how long to wait for the next byte in microseconds before
+    /// calling `timed_out`, or `None` if there is no limit.
+    pub fn timeout(&self) -> Option<u64> {
+        match self.state {
+            TxState::Waiting => self.chan.config.initial_nak_wait,
+            _ => self.chan.config.byte_timeout,
+        }
+    }
+
+    /// Returns the bytes waiting to be sent to the receiver.
+    pub fn poll_transmit(&self) -> &[u8] { self.chan.out.pending() }
+
+    /// Marks the first `n` bytes returned by `poll_transmit` as sent.
+    pub fn transmitted(&mut self, n: usize) { self.chan.out.advance(n) }
+
+    /// Returns the next event, if any. Events should be polled after every
+    /// call feeding the transmitter; at most four are held.
+    pub fn poll_event(&mut self) -> Option<Event> { self.chan.events.pop() }
+
+    /// Queues `data` as the next packet. Data longer than 128 bytes is sent
+    /// as a 1024 byte `STX` (XMODEM-1K) packet. Packets are padded with
+    /// `config.padding`. After a rejection, the packet replaces the rejected
+    /// one and reuses its number. If its block size differs, as when falling
+    /// back from 1024 to 128 byte packets, it gets `config.max_retries`
+    /// attempts of its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the transmitter is not
+    /// ready or if `data` is empty or longer than 1024 bytes, and an error of
+    /// kind `WouldBlock` if the output of earlier calls leaves no room for
+    /// the packet.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() || data.len() > BUF_LEN_1K {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet data must hold between 1 and 1024 bytes",
+            ));
+        }
+
+        self.ready()?;
+        let len = match data.len() > BUF_LEN {
+            true => BUF_LEN_1K,
+            false => BUF_LEN,
+        };
+        if self.state != TxState::Waiting {
+            self.reserve_packet(len)?;
+        }
+        if self.state == TxState::Rejected && len != self.len {
+            self.attempts = 0;
+        }
+
+        self.len = len;
+        self.data[..data.len()].copy_from_slice(data);
+        let padding = self.chan.config.padding;
+        self.data[data.len()..self.len]
+            .iter_mut()
+            .for_each(|b| *b = padding);
+
+        match self.state {
+            TxState::Waiting => self.queued = Queued::Packet,
+            _ => self.load()?,
+        }
+
+        Ok(())
+    }
+
+    /// Queues the rejected packet again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the last packet was not
+    /// rejected, and an error of kind `WouldBlock` if the output of earlier
+    /// calls leaves no room for the packet.
+    pub fn resend(&mut self) -> io::Result<()> {
+        if self.state != TxState::Rejected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no packet was rejected",
+            ));
+        }
+
+        self.load()
+    }
+
+    /// Queues end of transmission.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the transmitter is not
+    /// ready, and an error of kind `WouldBlock` if the events or output of
+    /// earlier calls haven't been taken with `poll_event` and `transmitted`.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.ready()?;
+        self.chan.reserve()?;
+        match self.state {
+            TxState::Waiting => self.queued = Queued::Eot,
+            _ => self.eot()?,
+        }
+
+        Ok(())
+    }
+
+    /// Cancels the session because of `reason`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock`, without cancelling the session,
+    /// if the events or output of earlier calls haven't been taken with
+    /// `poll_event` and `transmitted`.
+    pub fn cancel(&mut self, reason: &'static str) -> io::Result<()> {
+        self.chan.reserve()?;
+        if !self.is_finished() {
+            self.chan.cancel(reason)?;
+            self.state = TxState::Aborted;
+        }
+
+        Ok(())
+    }
+
+    /// Feeds a byte received from the receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock`, without consuming `byte`, if
+    /// the events or output of earlier calls haven't been taken with
+    /// `poll_event` and `transmitted`.
+    pub fn receive_byte(&mut self, byte: u8) -> io::Result<()> {
+        self.chan.reserve()?;
+        match self.state {
+            TxState::Waiting => self.start(byte)?,
+            TxState::Ready | TxState::Rejected if byte == CAN => {
+                self.cancelled()?
+            }
+            TxState::Ready | TxState::Rejected => {}
+            TxState::AwaitAck => match byte {
+                ACK => {
+                    self.chan.events.push(Event::PacketSent {
+                        packet: self.packet,
+                        len:    self.len,
+                    })?;
+                    self.packet = self.packet.wrapping_add(1);
+                    self.attempts = 0;
+                    self.state = TxState::Ready;
+                }
+                NAK => self.reject(RetryReason::Nak)?,
+                CAN => self.cancelled()?,
+                _ => self.abort(
+                    io::ErrorKind::InvalidData,
+                    "Expected ACK or NAK after packet transmission",
+                )?,
+            },
+            TxState::FirstEot => match byte {
+                NAK => {
+                    self.chan.out.push(&[EOT])?;
+                    self.state = TxState::SecondEot;
+                }
+                CAN => self.cancelled()?,
+                _ => self.abort(
+                    io::ErrorKind::InvalidData,
+                    "Expected NAK after first EOT",
+                )?,
+            },
+            TxState::SecondEot => match byte {
+                ACK => {
+                    self.chan.events.push(Event::Done)?;
+                    self.state = TxState::Done;
+                }
+                CAN => self.cancelled()?,
+                _ => self.abort(
+                    io::ErrorKind::InvalidData,
+                    "Expected ACK after second EOT",
+                )?,
+            },
+            TxState::Done | TxState::Aborted => {}
+        }
+
+        Ok(())
+    }
+
+    /// Reports that no byte arrived within `timeout()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock`, without handling the timeout,
+    /// if the events or output of earlier calls haven't been taken with
+    /// `poll_event` and `transmitted`.
+    pub fn timed_out(&mut self) -> io::Result<()> {
+        self.chan.reserve()?;
+        match self.state {
+            TxState::AwaitAck => self.reject(RetryReason::Timeout),
+            TxState::Waiting | TxState::FirstEot | TxState::SecondEot => {
+                let msg = "timed out waiting for the receiver";
+                self.abort(io::ErrorKind::TimedOut, msg)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the transmitter is not ready.
+    fn ready(&self) -> io::Result<()> {
+        match self.is_ready() {
+            true => Ok(()),
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "transmitter is not ready for another packet",
+            )),
+        }
+    }
+
+    /// Handles the receiver's first byte, which selects the checksum, and
+    /// sends whatever was queued.
+    fn start(&mut self, byte: u8) -> io::Result<()> {
+        self.checksum = match byte {
+            NAK => Checksum::Standard,
+            CRC => Checksum::Crc16,
+            CAN => return self.cancelled(),
+            _ => {
+                return self.abort(
+                    io::ErrorKind::InvalidData,
+                    "transmission must start with NAK or C",
+                )
+            }
+        };
+
+        self.chan.events.push(Event::Started)?;
+        self.state = TxState::Ready;
+        match self.queued {
+            Queued::Nothing => Ok(()),
+            Queued::Packet => self.load(),
+            Queued::Eot => self.eot(),
+        }
+    }
+
+    /// Checks that a packet holding `len` bytes of data can be queued behind
+    /// the pending output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `WouldBlock` if too much of the output of
+    /// earlier calls hasn't been transmitted.
+    fn reserve_packet(&self, len: usize) -> io::Result<()> {
+        if self.chan.out.room() < 3 + len + self.checksum.size() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "output was not transmitted",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Queues the current packet for sending after any pending output.
+    fn load(&mut self) -> io::Result<()> {
+        self.reserve_packet(self.len)?;
+        let header = match self.len {
+            BUF_LEN_1K => STX,
+            _ => SOH,
+        };
+
+        let mut checksum = [0u8; 2];
+        self.checksum.compute(&self.data[..self.len], &mut checksum);
+
+        let out = &mut self.chan.out;
+        out.push(&[header, self.packet, 255 - self.packet])?;
+        out.push(&self.data[..self.len])?;
+        out.push(&checksum[..self.checksum.size()])?;
+        self.state = TxState::AwaitAck;
+        Ok(())
+    }
+
+    /// Queues the first `EOT`.
+    fn eot(&mut self) -> io::Result<()> {
+        self.chan.events.push(Event::Terminated)?;
+        self.chan.out.push(&[EOT])?;
+        self.state = TxState::FirstEot;
+        Ok(())
+    }
+
+    /// Reports that the current packet must be sent again because of
+    /// `reason`, or cancels the session if it was rejected
+    /// `config.max_retries` times.
+    fn reject(&mut self, reason: RetryReason) -> io::Result<()> {
+        self.attempts += 1;
+        if self.attempts >= self.chan.config.max_retries {
+            self.state = TxState::Aborted;
+            return self.chan.fail(io::ErrorKind::BrokenPipe, "bad transmit");
+        }
+
+        self.chan.events.push(Event::Retry {
+            packet: self.packet,
+            reason,
+        })?;
+        self.state = TxState::Rejected;
+        Ok(())
+    }
+
+    fn cancelled(&mut self) -> io::Result<()> {
+        self.state = TxState::Aborted;
+        self.chan.cancelled()
+    }
+
+    fn abort(
+        &mut self,
+        kind: io::ErrorKind,
+        msg: &'static str,
+    ) -> io::Result<()> {
+        self.state = TxState::Aborted;
+        self.chan.abort(kind, msg)
+    }
+}
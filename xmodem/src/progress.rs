@@ -4,18 +4,73 @@
 /// methods like [`Xmodem::transmit_with_progress()`],
 /// [`Xmodem::receive_with_progress()`], and [`Xmodem::new_with_progress()`]. It
 /// is intended to be used by progress indicators or for debugging purposes.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Progress {
     /// Waiting for receiver to send NAK.
     Waiting,
     /// Download/upload has started.
     Started,
-    /// Packet `.0` was transmitted/received.
-    Packet(u8),
-    Terminated(u8),
+    /// Packet `packet` holding `size` bytes of data (128 or 1024) was
+    /// transmitted/received.
+    Packet {
+        packet: u8,
+        size:   usize,
+        stats:  Stats,
+    },
+    /// Packet `packet` is being transmitted/requested again because of
+    /// `reason`.
+    Retry {
+        packet: u8,
+        reason: RetryReason,
+        stats:  Stats,
+    },
+    /// The session was cancelled with `CAN` because of `reason`.
+    Cancelled {
+        reason: CancelReason,
+        stats:  Stats,
+    },
+    /// End of transmission was sent/received.
+    Terminated { stats: Stats },
 }
 
-/// Type for progress callbacks.
+/// Statistics describing a transfer so far.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of data bytes transferred, including padding.
+    pub bytes:   usize,
+    /// The total number of data bytes to transfer, if known.
+    pub total:   Option<usize>,
+    /// The number of packets transferred.
+    pub packets: usize,
+    /// The number of packets transmitted/requested again.
+    pub retries: usize,
+    /// The time elapsed since the transfer started in microseconds, as
+    /// measured by the session's clock.
+    pub elapsed: u64,
+}
+
+/// The reason a packet is transmitted/requested again.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RetryReason {
+    /// The receiver answered the packet with `NAK`.
+    Nak,
+    /// The received packet's checksum did not match; `NAK` was sent.
+    Checksum,
+    /// The packet or its acknowledgement did not arrive in time.
+    Timeout,
+}
+
+/// The reason a session was cancelled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The other side sent `CAN`.
+    Received,
+    /// `CAN` was sent to the other side for the given reason.
+    Sent(&'static str),
+}
+
+/// Type for plain function progress callbacks. Any `FnMut(Progress)` may be
+/// used as a progress callback.
 pub type ProgressFn = fn(Progress);
 
 /// Noop progress callback.
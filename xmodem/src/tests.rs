@@ -1,5 +1,6 @@
 use super::*;
-use std::io::Cursor;
+use std::io::{self, Cursor};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
 
 struct Pipe(Sender<u8>, Receiver<u8>, Vec<u8>);
@@ -38,6 +39,54 @@ impl io::Write for Pipe {
     fn flush(&mut self) -> io::Result<()> { Ok(()) }
 }
 
+/// A reader/writer that reads from a fixed script of bytes and records every
+/// byte written to it. The first `.2` reads fail with `TimedOut`.
+struct Script(Cursor<Vec<u8>>, Vec<u8>, usize);
+
+fn script(input: Vec<u8>) -> Script { Script(Cursor::new(input), vec![], 0) }
+
+impl io::Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.2 > 0 {
+            self.2 -= 1;
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        }
+
+        self.0.read(buf)
+    }
+}
+
+impl io::Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.1.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/// A reader that never has data available.
+struct Stall(Vec<u8>);
+
+impl io::Read for Stall {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::WouldBlock, "no data"))
+    }
+}
+
+impl io::Write for Stall {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/// A clock that advances by one millisecond every time it is read.
+fn ticking_clock() -> u64 {
+    static TICKS: AtomicUsize = AtomicUsize::new(0);
+    TICKS.fetch_add(1000, Ordering::SeqCst) as u64
+}
+
 #[test]
 fn test_loop() {
     let mut input = [0u8; 384];
@@ -79,6 +128,49 @@ fn test_transmit_reported_bytes() {
     );
 }
 
+#[test]
+fn test_receive_keeps_trailing_zeros() {
+    let mut input: Vec<u8> = (0..200).map(|i| i as u8 | 1).collect();
+    input[190..].iter_mut().for_each(|b| *b = 0);
+    let expected = input.clone();
+    let (tx, rx) = pipe();
+    let tx_thread =
+        std::thread::spawn(move || Xmodem::transmit(&input[..], rx));
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive(tx, &mut output).map(|n| (n, output))
+    });
+
+    assert_eq!(tx_thread.join().expect("tx join").expect("tx okay"), 200);
+    let (n, output) = rx_thread.join().expect("rx join").expect("rx okay");
+    assert_eq!(n, 256);
+    assert_eq!(&output[..200], &expected[..]);
+    assert!(output[200..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn test_receive_trims_padding() {
+    let input: Vec<u8> = (0..130).map(|i| i as u8 | 1).collect();
+    let expected = input.clone();
+    let (tx, rx) = pipe();
+    let tx_thread =
+        std::thread::spawn(move || Xmodem::transmit(&input[..], rx));
+    let rx_thread = std::thread::spawn(move || {
+        let config = XmodemConfig {
+            trim: Trim::Padding,
+            ..XmodemConfig::default()
+        };
+        let mut output = vec![];
+        Xmodem::receive_with_config(tx, &mut output, config, |_| ())
+            .map(|n| (n, output))
+    });
+
+    assert_eq!(tx_thread.join().expect("tx join").expect("tx okay"), 130);
+    let (n, output) = rx_thread.join().expect("rx join").expect("rx okay");
+    assert_eq!(n, 130);
+    assert_eq!(output, expected);
+}
+
 #[test]
 fn test_raw_transmission() {
     let mut input = [0u8; 128];
@@ -136,29 +228,817 @@ fn test_eot() {
     assert_eq!(&buffer[..], &[NAK, EOT, NAK, EOT, ACK]);
 }
 
+fn events<F: FnMut() -> Option<Event>>(mut poll: F) -> Vec<Event> {
+    let mut events = vec![];
+    while let Some(event) = poll() {
+        events.push(event);
+    }
+
+    events
+}
+
 #[test]
-fn test_expect_byte() {
-    let mut xmodem = Xmodem::new(Cursor::new(vec![1, 1]));
-    assert_eq!(xmodem.expect_byte(1, "1").expect("expected"), 1);
-    let e = xmodem
-        .expect_byte(2, "1, please")
-        .expect_err("expect the unexpected");
+fn test_receiver_machine() {
+    let mut receiver = super::Receiver::new(XmodemConfig::default());
+    assert_eq!(receiver.poll_transmit(), &[NAK]);
+    receiver.transmitted(1);
+    assert_eq!(receiver.poll_transmit(), &[]);
+
+    let data = [0x42u8; 128];
+    receiver.receive_byte(SOH).expect("feed byte");
+    receiver.receive_byte(1).expect("feed byte");
+    receiver.receive_byte(254).expect("feed byte");
+    for b in data.iter() {
+        receiver.receive_byte(*b).expect("feed byte");
+    }
+    assert!(events(|| receiver.poll_event()).contains(&Event::Started));
+
+    let mut checksum = [0u8; 1];
+    Checksum::Standard.compute(&data, &mut checksum);
+    receiver.receive_byte(checksum[0]).expect("feed byte");
+    assert_eq!(receiver.poll_transmit(), &[ACK]);
+    assert_eq!(
+        events(|| receiver.poll_event()),
+        vec![Event::PacketReceived { packet: 1, len: 128 }]
+    );
+    assert_eq!(receiver.data(), &data[..]);
+    receiver.transmitted(1);
+
+    receiver.receive_byte(EOT).expect("feed byte");
+    assert_eq!(receiver.poll_transmit(), &[NAK]);
+    receiver.transmitted(1);
+    receiver.receive_byte(EOT).expect("feed byte");
+    assert_eq!(receiver.poll_transmit(), &[ACK]);
+    assert_eq!(
+        events(|| receiver.poll_event()),
+        vec![Event::Terminated, Event::Done]
+    );
+    assert!(receiver.is_finished());
+}
+
+#[test]
+fn test_receiver_machine_unexpected_byte() {
+    let mut receiver = super::Receiver::new(XmodemConfig::default());
+    receiver.transmitted(1);
+    receiver.receive_byte(0xFF).expect("feed byte");
+    assert_eq!(
+        events(|| receiver.poll_event()).last(),
+        Some(&Event::Aborted {
+            kind: io::ErrorKind::InvalidData,
+            msg:  "Neither SOH, STX nor EOT recieved",
+        })
+    );
+    assert!(receiver.is_finished());
+}
+
+#[test]
+fn test_transmitter_machine() {
+    let mut transmitter = super::Transmitter::new(XmodemConfig::default());
+    assert!(!transmitter.is_started());
+    transmitter.send(&[0x42; 100]).expect("queue packet");
+    assert_eq!(transmitter.poll_transmit(), &[]);
+
+    transmitter.receive_byte(NAK).expect("feed byte");
+    assert_eq!(events(|| transmitter.poll_event()), vec![Event::Started]);
+    let packet = transmitter.poll_transmit().to_vec();
+    assert_eq!(packet.len(), 132);
+    assert_eq!(&packet[..3], &[SOH, 1, 254]);
+    assert_eq!(&packet[3..103], &[0x42; 100][..]);
+    assert!(packet[103..131].iter().all(|b| *b == 0));
+    transmitter.transmitted(packet.len());
+
+    transmitter.receive_byte(NAK).expect("feed byte");
+    assert_eq!(
+        events(|| transmitter.poll_event()),
+        vec![Event::Retry {
+            packet: 1,
+            reason: RetryReason::Nak,
+        }]
+    );
+    transmitter.resend().expect("resend packet");
+    assert_eq!(transmitter.poll_transmit(), &packet[..]);
+    transmitter.transmitted(packet.len());
+
+    transmitter.receive_byte(ACK).expect("feed byte");
+    assert_eq!(
+        events(|| transmitter.poll_event()),
+        vec![Event::PacketSent { packet: 1, len: 128 }]
+    );
+    assert!(transmitter.is_ready());
+
+    transmitter.finish().expect("end transmission");
+    assert_eq!(transmitter.poll_transmit(), &[EOT]);
+    transmitter.transmitted(1);
+    transmitter.receive_byte(NAK).expect("feed byte");
+    assert_eq!(transmitter.poll_transmit(), &[EOT]);
+    transmitter.transmitted(1);
+    transmitter.receive_byte(ACK).expect("feed byte");
+    assert_eq!(
+        events(|| transmitter.poll_event()),
+        vec![Event::Terminated, Event::Done]
+    );
+    assert!(transmitter.is_finished());
+}
+
+#[test]
+fn test_transmitter_machine_unexpected_byte() {
+    let mut transmitter = super::Transmitter::new(XmodemConfig::default());
+    transmitter.receive_byte(0xFF).expect("feed byte");
+    assert_eq!(
+        events(|| transmitter.poll_event()),
+        vec![Event::Aborted {
+            kind: io::ErrorKind::InvalidData,
+            msg:  "transmission must start with NAK or C",
+        }]
+    );
+    assert!(transmitter.is_finished());
+
+    let mut transmitter = super::Transmitter::new(XmodemConfig::default());
+    transmitter.receive_byte(CAN).expect("feed byte");
+    assert_eq!(
+        events(|| transmitter.poll_event()),
+        vec![
+            Event::Cancelled(CancelReason::Received),
+            Event::Aborted {
+                kind: io::ErrorKind::ConnectionAborted,
+                msg:  "received CAN",
+            },
+        ]
+    );
+    assert!(transmitter.send(&[0; 128]).is_err());
+}
+
+#[test]
+fn test_machine_backpressure() {
+    let config = XmodemConfig {
+        max_retries: 2000,
+        ..XmodemConfig::default()
+    };
+
+    // Every unanswered probe queues another NAK until the output is full.
+    let mut receiver = super::Receiver::new(config);
+    let mut probes = 1;
+    let e = loop {
+        match receiver.timed_out() {
+            Ok(()) => probes += 1,
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(receiver.poll_transmit().len(), probes);
+    assert!(receiver.poll_transmit().iter().all(|b| *b == NAK));
+    receiver.transmitted(probes);
+    receiver.timed_out().expect("output transmitted");
+
+    // Events that aren't polled are kept and further input is refused.
+    let mut receiver = super::Receiver::new(XmodemConfig::default());
+    receiver.transmitted(1);
+    receiver.receive_byte(SOH).expect("feed byte");
+    receiver.timed_out().expect("first timeout");
+    let e = receiver.receive_byte(SOH).expect_err("events not polled");
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(
+        events(|| receiver.poll_event()),
+        vec![
+            Event::Started,
+            Event::Retry {
+                packet: 1,
+                reason: RetryReason::Timeout,
+            },
+        ]
+    );
+    receiver.receive_byte(SOH).expect("events polled");
+}
+
+#[test]
+fn test_transmitter_keeps_pending_output() {
+    let mut transmitter = super::Transmitter::new(XmodemConfig::default());
+    transmitter.receive_byte(NAK).expect("start");
+    transmitter.send(&[1; 128]).expect("send");
+    let packet = transmitter.poll_transmit().to_vec();
+    assert_eq!(packet.len(), 132);
+
+    // A packet rejected before it was transmitted is queued again behind it.
+    transmitter.receive_byte(NAK).expect("reject");
+    transmitter.resend().expect("resend");
+    let twice = [&packet[..], &packet[..]].concat();
+    assert_eq!(transmitter.poll_transmit(), &twice[..]);
+
+    // A 1K packet only fits once the earlier output was transmitted.
+    events(|| transmitter.poll_event());
+    transmitter.receive_byte(ACK).expect("acknowledge");
+    let e = transmitter.send(&[2; 1024]).expect_err("output pending");
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(transmitter.poll_transmit(), &twice[..]);
+
+    transmitter.transmitted(twice.len());
+    transmitter.send(&[2; 1024]).expect("output transmitted");
+    assert_eq!(transmitter.poll_transmit().len(), 1028);
+    assert_eq!(&transmitter.poll_transmit()[..3], &[STX, 2, 253]);
+}
+
+#[test]
+fn test_mixed_1k_loop() {
+    let mut input = vec![0u8; 2 * 1024 + 300];
+    for (i, b) in input.iter_mut().enumerate() {
+        *b = (i % 251) as u8;
+    }
+
+    let expected = input.clone();
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let n = Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        (n, rx.2)
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive(&mut tx, &mut output).map(|n| (n, output))
+    });
+
+    let (written, rx_buf) = tx_thread.join().expect("tx join okay");
+    let (received, output) =
+        rx_thread.join().expect("rx join okay").expect("rx okay");
+
+    assert_eq!(written, 2 * 1024 + 300);
+    assert_eq!(received, 2 * 1024 + 3 * 128);
+    assert_eq!(&output[..written], &expected[..]);
+    assert!(output[written..].iter().all(|b| *b == 0));
+
+    // Two 1K packets followed by three 128 byte packets and two EOTs.
+    assert_eq!(rx_buf.len(), 2 * (1024 + 4) + 3 * (128 + 4) + 2);
+    assert_eq!(&rx_buf[0..3], &[STX, 1, 255 - 1]);
+    assert_eq!(&rx_buf[1028..1031], &[STX, 2, 255 - 2]);
+    assert_eq!(&rx_buf[2056..2059], &[SOH, 3, 255 - 3]);
+    assert_eq!(&rx_buf[2188..2191], &[SOH, 4, 255 - 4]);
+    assert_eq!(&rx_buf[2320..2323], &[SOH, 5, 255 - 5]);
+    assert_eq!(&rx_buf[2452..], &[EOT, EOT]);
+}
+
+#[test]
+fn test_1k_fallback_on_nak() {
+    let input = [7u8; 1024];
+    let mut responses = vec![NAK, NAK, NAK, NAK];
+    // The first 128 byte packet still gets all of its attempts.
+    responses.extend_from_slice(&[NAK; 9]);
+    responses.extend_from_slice(&[ACK; 8]);
+    responses.extend_from_slice(&[NAK, ACK]);
+
+    let mut to = script(responses);
+    let written = Xmodem::transmit(&input[..], &mut to).expect("transmit");
+    assert_eq!(written, 1024);
+
+    // The 1K packet rejected three times, then eight 128 byte packets reusing
+    // its number, the first of them sent ten times.
+    let sent = to.1;
+    for i in 0..3 {
+        assert_eq!(&sent[i * 1028..i * 1028 + 3], &[STX, 1, 255 - 1]);
+    }
+    let packets = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 3, 4, 5, 6, 7, 8];
+    for (i, &packet) in packets.iter().enumerate() {
+        let start = 3 * 1028 + i * 132;
+        assert_eq!(&sent[start..start + 3], &[SOH, packet, 255 - packet]);
+        assert_eq!(sent[start + 131], checksum::additive(&[7; 128]));
+    }
+    assert_eq!(&sent[3 * 1028 + packets.len() * 132..], &[EOT, EOT]);
+}
+
+#[test]
+fn test_1k_retry_keeps_1k() {
+    let input: Vec<u8> = (0..3 * 1024).map(|i| (i % 253) as u8).collect();
+
+    // The first packet is corrupted once. Once a 1K packet was accepted,
+    // even repeated rejections do not cause a fallback.
+    let mut responses = vec![NAK, NAK, ACK, NAK, NAK, NAK, NAK, ACK, ACK];
+    responses.extend_from_slice(&[NAK, ACK]);
+
+    let mut to = script(responses);
+    let written = Xmodem::transmit(&input[..], &mut to).expect("transmit");
+    assert_eq!(written, 3 * 1024);
+
+    let sent = to.1;
+    let packets = [1, 1, 2, 2, 2, 2, 2, 3];
+    assert_eq!(sent.len(), packets.len() * 1028 + 2);
+    for (i, &packet) in packets.iter().enumerate() {
+        let start = i * 1028;
+        let data = &input[(packet as usize - 1) * 1024..][..1024];
+        assert_eq!(&sent[start..start + 3], &[STX, packet, 255 - packet]);
+        assert_eq!(&sent[start + 3..start + 1027], data);
+    }
+    assert_eq!(&sent[packets.len() * 1028..], &[EOT, EOT]);
+}
+
+#[test]
+fn test_read_1k_packet() {
+    let mut input = vec![STX, 1, 255 - 1];
+    input.extend_from_slice(&[3; 1024]);
+    input.push(checksum::additive(&[3; 1024]));
+    input.extend_from_slice(&[SOH, 2, 255 - 2]);
+    input.extend_from_slice(&[1; 128]);
+    input.push(checksum::additive(&[1; 128]));
+
+    let mut xmodem = Xmodem::new(script(input));
+    let mut buffer = [0u8; 1024];
+    assert_eq!(xmodem.read_packet(&mut buffer).expect("1K packet"), 1024);
+    assert!(buffer.iter().all(|b| *b == 3));
+    assert_eq!(xmodem.read_packet(&mut buffer).expect("packet"), 128);
+    assert!(buffer[..128].iter().all(|b| *b == 1));
+    assert_eq!(&xmodem.inner.1, &[NAK, ACK, ACK]);
+}
+
+#[test]
+fn test_1k_packet_small_buffer() {
+    let mut xmodem = Xmodem::new(script(vec![STX, 1, 255 - 1]));
+    let mut buffer = [0u8; 128];
+    let e = xmodem.read_packet(&mut buffer).expect_err("buffer too small");
+    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(&xmodem.inner.1, &[NAK, CAN]);
+}
+
+#[test]
+fn test_crc16() {
+    assert_eq!(checksum::crc16(b"123456789"), 0x31C3);
+    assert_eq!(checksum::crc16(&[]), 0);
+}
+
+#[test]
+fn test_crc_raw_transmission() {
+    let mut input = [0u8; 128];
+    (0..128).for_each(|i| input[i] = i as u8);
+    let config = XmodemConfig {
+        checksum: Checksum::Crc16,
+        ..XmodemConfig::default()
+    };
+
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        rx.2
+    });
+
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = [0u8; 128];
+        Xmodem::receive_with_config(&mut tx, &mut output[..], config, |_| ())
+            .expect("receive okay");
+        (tx.2, output)
+    });
+
+    let rx_buf = tx_thread.join().expect("tx join okay");
+    let (tx_buf, output) = rx_thread.join().expect("rx join okay");
+
+    let crc = checksum::crc16(&input);
+    assert_eq!(rx_buf.len(), 135);
+    assert_eq!(&rx_buf[0..3], &[SOH, 1, 255 - 1]);
+    assert_eq!(&rx_buf[3..131], &input[..]);
+    assert_eq!(&rx_buf[131..133], &[(crc >> 8) as u8, crc as u8]);
+    assert_eq!(&rx_buf[133..135], &[EOT, EOT]);
+    assert_eq!(&tx_buf, &[CRC, ACK, NAK, ACK]);
+    assert_eq!(&output[..], &input[..]);
+}
+
+#[test]
+fn test_crc_fallback_to_checksum() {
+    let mut input = vec![SOH, 1, 255 - 1];
+    input.extend_from_slice(&[9; 128]);
+    input.push(checksum::additive(&[9; 128]));
+
+    let config = XmodemConfig {
+        checksum: Checksum::Crc16,
+        crc_probes: 2,
+        ..XmodemConfig::default()
+    };
+    let mut from = script(input);
+    from.2 = 2;
+
+    let mut xmodem = Xmodem::new_with_config(from, config);
+    let mut buffer = [0u8; 128];
+    assert_eq!(xmodem.read_packet(&mut buffer).expect("packet"), 128);
+    assert_eq!(xmodem.receiver.checksum(), Checksum::Standard);
+    assert_eq!(&xmodem.inner.1, &[CRC, CRC, NAK, ACK]);
+}
+
+#[test]
+fn test_start_timeout() {
+    let config = XmodemConfig {
+        max_retries: 3,
+        ..XmodemConfig::default()
+    };
+    let mut from = script(vec![]);
+    from.2 = 3;
+
+    let mut xmodem = Xmodem::new_with_config(from, config);
+    let e = xmodem.read_packet(&mut [0u8; 128]).expect_err("timed out");
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(&xmodem.inner.1, &[NAK, NAK, NAK]);
+}
+
+#[test]
+fn test_bad_start_byte() {
+    let mut xmodem = Xmodem::new(script(vec![ACK]));
+    let e = xmodem.write_packet(&[0; 128]).expect_err("bad start");
     assert_eq!(e.kind(), io::ErrorKind::InvalidData);
 }
 
 #[test]
-fn test_expect_byte_or_cancel() {
-    let mut buffer = vec![2, 0];
-    let b = Xmodem::new(Cursor::new(buffer.as_mut_slice()))
-        .expect_byte_or_cancel(2, "it's a 2")
-        .expect("got a 2");
+fn test_ymodem_batch() {
+    let kernel: Vec<u8> = (0..1500).map(|i| (i % 256) as u8).collect();
+    let config = b"arm_64bit=1\n".to_vec();
+    let files = vec![
+        (
+            FileInfo {
+                name:  "kernel8.img".to_string(),
+                len:   kernel.len() as u64,
+                mtime: Some(1518998400),
+            },
+            kernel,
+        ),
+        (FileInfo::new("config.txt", config.len() as u64), config),
+    ];
+
+    let expected = files.clone();
+    let (tx, rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let mut ymodem = Ymodem::new(rx);
+        for &(ref info, ref data) in files.iter() {
+            let sent = ymodem.send(info, &data[..]).expect("send okay");
+            assert_eq!(sent, data.len());
+        }
+        ymodem.finish().expect("finish okay");
+    });
+
+    let rx_thread = std::thread::spawn(move || {
+        let mut ymodem = Ymodem::new(tx);
+        let mut received = vec![];
+        loop {
+            let mut data = vec![];
+            match ymodem.receive(&mut data).expect("receive okay") {
+                Some(info) => received.push((info, data)),
+                None => return received,
+            }
+        }
+    });
+
+    tx_thread.join().expect("tx join okay");
+    let received = rx_thread.join().expect("rx join okay");
+    assert_eq!(received, expected);
+}
+
+#[test]
+fn test_ymodem_header_round_trip() {
+    let mut buffer = [0u8; 1024];
+    let info = FileInfo {
+        name:  "blackberry.bin".to_string(),
+        len:   4096,
+        mtime: Some(0o13237004510),
+    };
+
+    assert_eq!(info.encode(&mut buffer).expect("encode"), 128);
+    assert_eq!(&buffer[..15], b"blackberry.bin\0");
+    assert_eq!(&buffer[15..31], b"4096 13237004510");
+    let decoded = FileInfo::decode(&buffer[..128]).expect("decode");
+    assert_eq!(decoded, Some(info));
+
+    let long = FileInfo::new("x".repeat(200), 1);
+    assert_eq!(long.encode(&mut buffer).expect("encode"), 1024);
+    assert_eq!(FileInfo::decode(&buffer).expect("decode"), Some(long));
+
+    let e = FileInfo::new("", 1).encode(&mut buffer).expect_err("no name");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(FileInfo::decode(&[0; 128]).expect("decode"), None);
+
+    let e = FileInfo::decode(b"a\0twelve\0").expect_err("bad length");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_ymodem_length_mismatch() {
+    let (tx, rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let info = FileInfo::new("short", 10);
+        Ymodem::new(rx).send(&info, &[1u8; 5][..])
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut data = vec![];
+        Ymodem::new(tx).receive(&mut data).map(|_| data)
+    });
+
+    let e = tx_thread.join().expect("tx join").expect_err("length error");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    // The sender cancels instead of ending the file with EOT.
+    let e = rx_thread.join().expect("rx join").expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+}
+
+#[test]
+fn test_ymodem_length_mismatch_before_data() {
+    // The header is acknowledged, then the excess data is noticed before
+    // any of it is sent.
+    let mut ymodem = Ymodem::new(script(vec![CRC, ACK]));
+    let info = FileInfo::new("long", 128);
+    let e = ymodem.send(&info, &[1u8; 129][..]).expect_err("too long");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    let sent = ymodem.into_inner().1;
+    assert_eq!(&sent[..3], &[SOH, 0, 255]);
+    assert_eq!(&sent[133..], &[CAN]);
+}
+
+#[test]
+fn test_initial_nak_wait() {
+    let config = XmodemConfig {
+        initial_nak_wait: Some(5_000),
+        clock: ticking_clock,
+        ..XmodemConfig::default()
+    };
+
+    let mut xmodem = Xmodem::new_with_config(Stall(vec![]), config);
+    let e = xmodem.write_packet(&[0; 128]).expect_err("timed out");
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert!(xmodem.inner.0.is_empty());
+}
+
+#[test]
+fn test_byte_timeout_renaks_packet() {
+    let config = XmodemConfig {
+        byte_timeout: Some(5_000),
+        clock: ticking_clock,
+        ..XmodemConfig::default()
+    };
+
+    // The first packet stops short; the retransmission succeeds.
+    let mut input = vec![SOH, 1, 255 - 1, 4, 4];
+    input.extend_from_slice(&[SOH, 1, 255 - 1]);
+    input.extend_from_slice(&[4; 128]);
+    input.extend_from_slice(&[checksum::additive(&[4; 128]), EOT, EOT]);
+
+    struct Stutter(Cursor<Vec<u8>>, Vec<u8>);
+    impl io::Read for Stutter {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.position() {
+                5 if self.1.len() == 1 => Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "stalled",
+                )),
+                _ => self.0.read(&mut buf[..1]),
+            }
+        }
+    }
+    impl io::Write for Stutter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    let mut output = vec![];
+    let mut from = Stutter(Cursor::new(input), vec![]);
+    let received =
+        Xmodem::receive_with_config(&mut from, &mut output, config, |_| ())
+            .expect("receive okay");
+
+    assert_eq!(received, 128);
+    assert_eq!(&output[..], &[4; 128][..]);
+    assert_eq!(&from.1, &[NAK, NAK, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_max_retries_double_can() {
+    let config = XmodemConfig {
+        max_retries: 3,
+        double_can: true,
+        ..XmodemConfig::default()
+    };
+
+    let mut to = script(vec![NAK, NAK, NAK, NAK]);
+    let data = [1u8; 128];
+    let e = Xmodem::transmit_with_config(&data[..], &mut to, config, |_| ())
+        .expect_err("retries exhausted");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
 
-    assert_eq!(b, 2);
+    let sent = to.1;
+    assert_eq!(sent.len(), 3 * 132 + 2);
+    assert_eq!(&sent[3 * 132..], &[CAN, CAN]);
+}
+
+#[test]
+fn test_progress_closure_events() {
+    let mut events = vec![];
+    let config = XmodemConfig {
+        length_hint: Some(256),
+        clock: || 0,
+        ..XmodemConfig::default()
+    };
 
-    let e = Xmodem::new(Cursor::new(buffer.as_mut_slice()))
-        .expect_byte_or_cancel(0xFF, "not 0xFF")
-        .expect_err("unexpected");
+    let data = [5u8; 256];
+    let mut to = script(vec![NAK, ACK, NAK, ACK, NAK, ACK]);
+    Xmodem::transmit_with_config(&data[..], &mut to, config, |p| {
+        events.push(p)
+    }).expect("transmit okay");
+
+    let stats = |bytes, packets, retries| Stats {
+        bytes,
+        total: Some(256),
+        packets,
+        retries,
+        elapsed: 0,
+    };
+
+    assert_eq!(
+        events,
+        vec![
+            Progress::Waiting,
+            Progress::Started,
+            Progress::Packet {
+                packet: 1,
+                size:   128,
+                stats:  stats(128, 1, 0),
+            },
+            Progress::Retry {
+                packet: 2,
+                reason: RetryReason::Nak,
+                stats:  stats(128, 1, 1),
+            },
+            Progress::Packet {
+                packet: 2,
+                size:   128,
+                stats:  stats(256, 2, 1),
+            },
+            Progress::Terminated {
+                stats: stats(256, 2, 1),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_progress_elapsed() {
+    let config = XmodemConfig {
+        clock: ticking_clock,
+        ..XmodemConfig::default()
+    };
+
+    let mut last = None;
+    let mut to = script(vec![NAK, ACK, NAK, ACK]);
+    Xmodem::transmit_with_config(&[0u8; 128][..], &mut to, config, |p| {
+        if let Progress::Terminated { stats } = p {
+            last = Some(stats);
+        }
+    }).expect("transmit okay");
+
+    let stats = last.expect("terminated");
+    assert_eq!(stats.packets, 1);
+    assert!(stats.elapsed >= 1000);
+}
+
+#[test]
+fn test_progress_cancelled() {
+    let mut reasons = vec![];
+    let mut to = script(vec![NAK, CAN]);
+    let e = Xmodem::transmit_with_progress(&[0u8; 128][..], &mut to, |p| {
+        if let Progress::Cancelled { reason, .. } = p {
+            reasons.push(reason);
+        }
+    }).expect_err("cancelled");
+
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert_eq!(reasons, vec![CancelReason::Received]);
+
+    let mut reasons = vec![];
+    let mut from = script(vec![SOH, 2, 255 - 2]);
+    let e = Xmodem::receive_with_progress(&mut from, vec![], |p| {
+        if let Progress::Cancelled { reason, .. } = p {
+            reasons.push(reason);
+        }
+    }).expect_err("wrong packet");
 
     assert_eq!(e.kind(), io::ErrorKind::InvalidData);
-    assert_eq!(buffer[1], CAN);
+    assert_eq!(
+        reasons,
+        vec![CancelReason::Sent("Expected a different packet")]
+    );
+    assert_eq!(&from.1, &[NAK, CAN]);
+}
+
+#[test]
+fn test_byte_traits() {
+    use io::{ByteRead, ByteWrite};
+
+    /// A reader/writer implementing only the byte-oriented traits. Written
+    /// bytes are recorded in `.1`.
+    struct Bytes<'a>(Vec<u8>, &'a mut Vec<u8>);
+
+    impl<'a> ByteRead for Bytes<'a> {
+        fn read_byte(&mut self) -> io::Result<Option<u8>> {
+            match self.0.is_empty() {
+                true => Ok(None),
+                false => Ok(Some(self.0.remove(0))),
+            }
+        }
+    }
+
+    impl<'a> ByteWrite for Bytes<'a> {
+        fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+            Ok(self.1.push(byte))
+        }
+    }
+
+    let mut input = vec![SOH, 1, 255 - 1];
+    input.extend_from_slice(&[7; 128]);
+    input.extend_from_slice(&[checksum::additive(&[7; 128]), EOT, EOT]);
+
+    let (mut written, mut output) = (vec![], [0u8; 128]);
+    let n = Xmodem::receive(Bytes(input, &mut written), &mut output[..])
+        .expect("receive okay");
+    assert_eq!(n, 128);
+    assert_eq!(&output[..], &[7; 128][..]);
+    assert_eq!(&written, &[NAK, ACK, NAK, ACK]);
+
+    let (mut written, mut unused) = (vec![], vec![]);
+    let n = Xmodem::transmit(
+        Bytes(vec![7; 128], &mut unused),
+        Bytes(vec![NAK, ACK, NAK, ACK], &mut written),
+    ).expect("transmit okay");
+    assert_eq!(n, 128);
+    assert_eq!(written.len(), 134);
+    assert_eq!(&written[3..131], &[7; 128][..]);
+}
+
+/// Transmits `input` using `tx_config` and receives it using `rx_config`,
+/// returning the data written by the receiver.
+fn round_trip(
+    input: Vec<u8>,
+    tx_config: XmodemConfig,
+    rx_config: XmodemConfig,
+) -> Vec<u8> {
+    let (tx, rx) = pipe();
+    let len = input.len();
+    let tx_thread = std::thread::spawn(move || {
+        Xmodem::transmit_with_config(&input[..], rx, tx_config, progress::noop)
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive_with_config(tx, &mut output, rx_config, progress::noop)
+            .map(|n| (n, output))
+    });
+
+    assert_eq!(tx_thread.join().expect("tx join").expect("tx okay"), len);
+    let (n, output) = rx_thread.join().expect("rx join").expect("rx okay");
+    assert_eq!(n, output.len());
+    output
+}
+
+#[test]
+fn test_sub_padding_round_trip() {
+    let input = b"hello, world\n".to_vec();
+    let tx_config = XmodemConfig {
+        padding: SUB,
+        ..XmodemConfig::default()
+    };
+
+    let output = round_trip(input.clone(), tx_config, XmodemConfig::default());
+    assert_eq!(output.len(), 128);
+    assert_eq!(&output[..input.len()], &input[..]);
+    assert!(output[input.len()..].iter().all(|b| *b == SUB));
+
+    let rx_config = XmodemConfig {
+        padding: SUB,
+        trim: Trim::Padding,
+        ..XmodemConfig::default()
+    };
+    assert_eq!(round_trip(input.clone(), tx_config, rx_config), input);
+}
+
+#[test]
+fn test_padding_trim_keeps_inner_padding() {
+    let mut input = vec![1u8; 300];
+    input[100..260].iter_mut().for_each(|b| *b = 0);
+
+    let rx_config = XmodemConfig {
+        trim: Trim::Padding,
+        ..XmodemConfig::default()
+    };
+    let output = round_trip(input.clone(), XmodemConfig::default(), rx_config);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_length_hint_round_trip() {
+    let mut input = vec![0u8; 1200];
+    input[..1000].iter_mut().for_each(|b| *b = 0xAA);
+
+    let rx_config = XmodemConfig {
+        trim: Trim::Length,
+        length_hint: Some(input.len()),
+        ..XmodemConfig::default()
+    };
+    let output = round_trip(input.clone(), XmodemConfig::default(), rx_config);
+    assert_eq!(output, input);
+
+    let rx_config = XmodemConfig {
+        trim: Trim::Padding,
+        ..XmodemConfig::default()
+    };
+    let output = round_trip(input.clone(), XmodemConfig::default(), rx_config);
+    assert_eq!(&output[..], &input[..1000]);
 }
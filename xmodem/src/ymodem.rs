@@ -0,0 +1,263 @@
+use std::str;
+
+use checksum::Checksum;
+use config::XmodemConfig;
+use io::{self, ByteRead, ByteWrite};
+use progress::{self, Progress, ProgressFn};
+use {Xmodem, BUF_LEN, BUF_LEN_1K};
+
+/// Metadata describing a single file in a YMODEM batch.
+///
+/// This is the information carried by the header packet (packet `0`) that
+/// precedes every file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    /// The name of the file. Must not contain a NUL byte.
+    pub name:  String,
+    /// The exact length of the file in bytes.
+    pub len:   u64,
+    /// The modification time of the file in seconds since the UNIX epoch.
+    pub mtime: Option<u64>,
+}
+
+impl FileInfo {
+    /// Returns a new `FileInfo` for a file named `name` that is `len` bytes
+    /// long and has no modification time.
+    pub fn new<S: Into<String>>(name: S, len: u64) -> FileInfo {
+        FileInfo {
+            name: name.into(),
+            len,
+            mtime: None,
+        }
+    }
+
+    /// Encodes `self` into the header packet `buf`: the NUL terminated name
+    /// followed by the decimal length and, if present, the octal modification
+    /// time. Returns the size of the packet to send, 128 or 1024 bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the name is empty or
+    /// contains a NUL byte, or if the header does not fit in 1024 bytes.
+    pub(crate) fn encode(
+        &self,
+        buf: &mut [u8; BUF_LEN_1K],
+    ) -> io::Result<usize> {
+        if self.name.is_empty() || self.name.contains('\0') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file name must be non-empty and must not contain NUL",
+            ));
+        }
+
+        let fields = match self.mtime {
+            Some(mtime) => format!("{} {:o}", self.len, mtime),
+            None => format!("{}", self.len),
+        };
+
+        let name = self.name.as_bytes();
+        let len = name.len() + 1 + fields.len();
+        if len >= BUF_LEN_1K {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file header does not fit in a 1K packet",
+            ));
+        }
+
+        buf.iter_mut().for_each(|b| *b = 0);
+        buf[..name.len()].copy_from_slice(name);
+        buf[name.len() + 1..len].copy_from_slice(fields.as_bytes());
+
+        match len < BUF_LEN {
+            true => Ok(BUF_LEN),
+            false => Ok(BUF_LEN_1K),
+        }
+    }
+
+    /// Decodes a header packet. Returns `None` if the packet has an empty
+    /// name, marking the end of the batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the name is not valid UTF-8
+    /// or the length or modification time fields are malformed.
+    pub(crate) fn decode(buf: &[u8]) -> io::Result<Option<FileInfo>> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut parts = buf.splitn(2, |b| *b == 0);
+        let name = parts.next().unwrap_or(&[]);
+        if name.is_empty() {
+            return Ok(None);
+        }
+
+        let name = str::from_utf8(name)
+            .map_err(|_| invalid("file name is not valid UTF-8"))?;
+        let rest = parts.next().unwrap_or(&[]);
+        let rest = rest.split(|b| *b == 0).next().unwrap_or(&[]);
+        let rest = str::from_utf8(rest)
+            .map_err(|_| invalid("file header is not valid ASCII"))?;
+
+        let mut fields = rest.split(' ').filter(|f| !f.is_empty());
+        let len = fields
+            .next()
+            .ok_or_else(|| invalid("file header is missing the length"))?
+            .parse()
+            .map_err(|_| invalid("file length is not a decimal number"))?;
+        let mtime = match fields.next() {
+            Some(mtime) => Some(u64::from_str_radix(mtime, 8).map_err(|_| {
+                invalid("modification time is not an octal number")
+            })?),
+            None => None,
+        };
+
+        Ok(Some(FileInfo {
+            name: name.to_string(),
+            len,
+            mtime,
+        }))
+    }
+}
+
+/// Implementation of the YMODEM batch protocol on top of [`Xmodem`].
+///
+/// Every file is preceded by a header packet (packet `0`) announcing its name,
+/// length and modification time, so several files can be sent in a single
+/// session and the receiver can strip the padding from the final packet. A
+/// batch is ended by calling [`Ymodem::finish()`], which sends an empty
+/// header.
+pub struct Ymodem<T, P = ProgressFn> {
+    xmodem: Xmodem<T, P>,
+}
+
+impl<T: ByteRead + ByteWrite> Ymodem<T> {
+    /// Returns a new `Ymodem` instance with the internal reader/writer set to
+    /// `inner`. The returned instance requests `Checksum::Crc16` when
+    /// receiving, as YMODEM expects.
+    pub fn new(inner: T) -> Self {
+        let config = XmodemConfig {
+            checksum: Checksum::Crc16,
+            ..XmodemConfig::default()
+        };
+
+        Ymodem::new_with_config(inner, config)
+    }
+
+    /// Returns a new `Ymodem` instance with the internal reader/writer set to
+    /// `inner` using the session configuration `config`.
+    pub fn new_with_config(inner: T, config: XmodemConfig) -> Self {
+        Ymodem::new_with_progress(inner, config, progress::noop)
+    }
+}
+
+impl<T: ByteRead + ByteWrite, P: FnMut(Progress)> Ymodem<T, P> {
+    /// Returns a new `Ymodem` instance with the internal reader/writer set to
+    /// `inner` using the session configuration `config`. The function `f` is
+    /// used as a callback to indicate progress throughout each file transfer.
+    /// The total reported in `Stats` is the length announced by the file's
+    /// header. See the [`Progress`] enum for more information.
+    pub fn new_with_progress(inner: T, config: XmodemConfig, f: P) -> Self {
+        Ymodem {
+            xmodem: Xmodem::new_with_progress(inner, config, f),
+        }
+    }
+
+    /// Sends (uploads) the file described by `info` with contents `data`. The
+    /// header packet is sent first, followed by the contents of `data` and end
+    /// of transmission. Returns the number of bytes read from `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if `info` cannot be encoded in
+    /// a header packet. If the length of `data` differs from `info.len`, the
+    /// session is cancelled before end of transmission is sent and an error
+    /// of kind `InvalidData` is returned. Otherwise returns an error if
+    /// sending fails as described in [`Xmodem::write_packet()`].
+    pub fn send<R: ByteRead>(
+        &mut self,
+        info: &FileInfo,
+        data: R,
+    ) -> io::Result<usize> {
+        let mut header = [0u8; BUF_LEN_1K];
+        let len = info.encode(&mut header)?;
+        self.send_header(&header[..len])?;
+
+        self.xmodem.restart(1);
+        self.xmodem.reset_stats(Some(info.len as usize));
+        self.xmodem.send_data(data, Some(info.len as usize))
+    }
+
+    /// Ends the batch by sending an empty header packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending fails as described in
+    /// [`Xmodem::write_packet()`].
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.send_header(&[0u8; BUF_LEN])
+    }
+
+    /// Receives (downloads) the next file of the batch, writing its contents
+    /// into `into`. Exactly the number of bytes announced by the file's header
+    /// are written; the padding of the final packet is discarded. Returns the
+    /// file's header, or `None` if the sender ended the batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the header packet is
+    /// malformed or the sender ends the file before sending as many packets as
+    /// its announced length requires. Otherwise returns an error if receiving
+    /// fails as described in [`Xmodem::read_packet()`].
+    pub fn receive<W: ByteWrite>(
+        &mut self,
+        into: W,
+    ) -> io::Result<Option<FileInfo>> {
+        let mut header = [0u8; BUF_LEN_1K];
+        self.xmodem.restart(0);
+        let n = self.read_header(&mut header)?;
+
+        let info = match FileInfo::decode(&header[..n])? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let len = info.len as usize;
+        self.xmodem.restart(1);
+        self.xmodem.reset_stats(Some(len));
+        let received = self.xmodem.receive_data(into, Some(len))?;
+        if received as u64 != info.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file is shorter than its header",
+            ));
+        }
+
+        Ok(Some(info))
+    }
+
+    /// Returns the inner reader/writer, consuming `self`.
+    pub fn into_inner(self) -> T { self.xmodem.inner }
+
+    /// Sends the header packet `header` as packet `0`, retrying on checksum
+    /// mismatches.
+    fn send_header(&mut self, header: &[u8]) -> io::Result<()> {
+        self.xmodem.restart(0);
+        self.xmodem.write_packet_with_retry(header).map(|_| ())
+    }
+
+    /// Reads the header packet into `buf`, retrying on checksum mismatches.
+    /// Returns the size of the packet.
+    fn read_header(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.xmodem.read_packet(buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "expected a file header",
+                    ))
+                }
+                result => return result,
+            }
+        }
+    }
+}